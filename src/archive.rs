@@ -0,0 +1,271 @@
+use flate2::{Compression, write::GzEncoder};
+use log::warn;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Error, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+use zip::{DateTime, ZipWriter, write::SimpleFileOptions};
+
+use crate::{process::size_trans, temp::create_temp};
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Tar,
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl Format {
+    // infer the archive format from the output file name
+    fn from_path(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Format::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    files: usize,
+    dirs: usize,
+    links: usize,
+    bytes: u64,
+}
+
+/// Write matched entries into an archive, with paths relative to `root`.
+/// The format is inferred from the extension of `out`
+pub fn archive_entries(
+    root: &Path,
+    paths: &[PathBuf],
+    out: &Path,
+    size_fmt: &str,
+) -> Result<bool, Error> {
+    let Some(format) = Format::from_path(out) else {
+        eprintln!(
+            "error: unknown archive format `{}`, supported: .tar, .tar.gz, .tgz, .tar.zst, .zip",
+            out.display()
+        );
+        return Ok(false);
+    };
+
+    // skip the scan root itself and the archive in case it is written into the scanned tree
+    let out_abs = absolute_out(out);
+    let entries: Vec<(&PathBuf, &Path)> = paths
+        .iter()
+        .filter_map(|p| match p.strip_prefix(root) {
+            Ok(rel) if !rel.as_os_str().is_empty() => Some((p, rel)),
+            _ => None,
+        })
+        .filter(|(p, _)| out_abs.is_none() || fs::canonicalize(p).ok() != out_abs)
+        .collect();
+
+    // written to a temporary name and renamed on success, so a failed run leaves no partial
+    // archive and never clobbers an existing one
+    let (tmp, fp) = create_temp(out)?;
+    let res = write_archive(&format, BufWriter::new(fp), &entries)
+        .and_then(|summary| fs::rename(&tmp, out).map(|_| summary));
+    let summary = match res {
+        Ok(summary) => summary,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    };
+
+    eprintln!(
+        "archive `{}`: {} file(s), {} dir(s), {} symlink(s), {} -> {}",
+        out.display(),
+        summary.files,
+        summary.dirs,
+        summary.links,
+        size_trans(summary.bytes as f64, size_fmt),
+        size_trans(fs::metadata(out)?.len() as f64, size_fmt)
+    );
+    Ok(true)
+}
+
+fn write_archive<W: Write + io::Seek>(
+    format: &Format,
+    fp: W,
+    entries: &[(&PathBuf, &Path)],
+) -> Result<Summary, Error> {
+    Ok(match format {
+        Format::Tar => {
+            let (mut fp, summary) = write_tar(fp, entries)?;
+            fp.flush()?;
+            summary
+        }
+        Format::TarGz => {
+            let (enc, summary) = write_tar(GzEncoder::new(fp, Compression::default()), entries)?;
+            enc.finish()?.flush()?;
+            summary
+        }
+        Format::TarZst => {
+            let (enc, summary) = write_tar(zstd::Encoder::new(fp, 0)?, entries)?;
+            enc.finish()?.flush()?;
+            summary
+        }
+        Format::Zip => write_zip(fp, entries)?,
+    })
+}
+
+// the canonical path of the output file, whose parent is `.` for a bare file name
+fn absolute_out(out: &Path) -> Option<PathBuf> {
+    let parent = out
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::canonicalize(parent)
+        .map(|p| p.join(out.file_name().unwrap_or_default()))
+        .ok()
+}
+
+fn write_tar<W: Write>(fp: W, entries: &[(&PathBuf, &Path)]) -> Result<(W, Summary), Error> {
+    let mut summary = Summary::default();
+    let mut builder = tar::Builder::new(fp);
+    // store symlinks as links, mode, owner and mtime are kept in the headers
+    builder.follow_symlinks(false);
+
+    for (path, rel) in entries {
+        let meta = fs::symlink_metadata(path)?;
+        if meta.is_dir() {
+            builder.append_dir(rel, path)?;
+            summary.dirs += 1;
+        } else if meta.is_symlink() {
+            builder.append_path_with_name(path, rel)?;
+            summary.links += 1;
+        } else if meta.is_file() {
+            builder.append_path_with_name(path, rel)?;
+            summary.files += 1;
+            summary.bytes += meta.len();
+        } else {
+            warn!("skip special file `{}`", path.display());
+        }
+    }
+
+    let fp = builder.into_inner()?;
+    Ok((fp, summary))
+}
+
+fn write_zip<W: Write + io::Seek>(fp: W, entries: &[(&PathBuf, &Path)]) -> Result<Summary, Error> {
+    let mut summary = Summary::default();
+    let mut zip = ZipWriter::new(fp);
+
+    for (path, rel) in entries {
+        let meta = fs::symlink_metadata(path)?;
+        let name = rel.to_string_lossy();
+        let mut opts = SimpleFileOptions::default()
+            .unix_permissions(meta.permissions().mode())
+            .large_file(meta.len() >= u32::MAX as u64);
+        if let Some(mtime) = zip_time(&meta) {
+            opts = opts.last_modified_time(mtime);
+        }
+
+        if meta.is_dir() {
+            zip.add_directory(name, opts).map_err(Error::other)?;
+            summary.dirs += 1;
+        } else if meta.is_symlink() {
+            let target = fs::read_link(path)?;
+            zip.add_symlink(name, target.to_string_lossy(), opts)
+                .map_err(Error::other)?;
+            summary.links += 1;
+        } else if meta.is_file() {
+            zip.start_file(name, opts).map_err(Error::other)?;
+            io::copy(&mut File::open(path)?, &mut zip)?;
+            summary.files += 1;
+            summary.bytes += meta.len();
+        } else {
+            warn!("skip special file `{}`", path.display());
+        }
+    }
+
+    zip.finish().map_err(Error::other)?.flush()?;
+    Ok(summary)
+}
+
+// zip stores mtime as local date and time without a time zone
+fn zip_time(meta: &fs::Metadata) -> Option<DateTime> {
+    let mtime = jiff::Timestamp::try_from(meta.modified().ok()?).ok()?;
+    let dt = mtime.to_zoned(jiff::tz::TimeZone::system()).datetime();
+    DateTime::from_date_and_time(
+        dt.year() as u16,
+        dt.month() as u8,
+        dt.day() as u8,
+        dt.hour() as u8,
+        dt.minute() as u8,
+        dt.second() as u8,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            Format::from_path(Path::new("qc.tar.gz")),
+            Some(Format::TarGz)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("out/qc.TGZ")),
+            Some(Format::TarGz)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("qc.tar.zst")),
+            Some(Format::TarZst)
+        );
+        assert_eq!(Format::from_path(Path::new("qc.tar")), Some(Format::Tar));
+        assert_eq!(Format::from_path(Path::new("qc.zip")), Some(Format::Zip));
+        assert_eq!(Format::from_path(Path::new("qc.gz")), None);
+    }
+
+    #[test]
+    fn test_archive_entries() {
+        let root = TempDir::new("archive");
+        fs::write(root.join("a.txt"), "data").unwrap();
+        let fifo = std::ffi::CString::new(root.join("p").into_os_string().into_encoded_bytes());
+        assert_eq!(unsafe { libc::mkfifo(fifo.unwrap().as_ptr(), 0o600) }, 0);
+        let out = root.join("out.tar");
+        fs::write(&out, "previous run").unwrap();
+        let paths = vec![
+            root.to_path_buf(),
+            root.join("a.txt"),
+            out.clone(),
+            root.join("p"),
+        ];
+
+        for name in ["out.tar", "out.zip"] {
+            let out = root.join(name);
+            assert!(archive_entries(&root, &paths, &out, "b").unwrap());
+        }
+        let mut tar = tar::Archive::new(File::open(&out).unwrap());
+        let names: Vec<PathBuf> = tar
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert_eq!(names, vec![PathBuf::from("a.txt")]);
+        // only the two archives are left next to the entries, no temporary files
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 4);
+
+        assert_eq!(
+            absolute_out(Path::new("out.tar")),
+            Some(std::env::current_dir().unwrap().join("out.tar"))
+        );
+    }
+}
@@ -1,11 +1,16 @@
 use clap::{
-    ArgAction, Parser,
+    ArgAction, ArgGroup, Args, Parser, Subcommand, ValueEnum,
     builder::{
         Styles,
         styling::{AnsiColor, Effects},
     },
 };
 
+use crate::{
+    filetype::FileKind, hash::HashAlgo, magic::Kind, manifest::ManifestFormat, pattern::MatchOn,
+    transfer::Conflict,
+};
+
 // Configures Clap help menu colors
 const STYLES: Styles = Styles::styled()
     .header(AnsiColor::Green.on_default().effects(Effects::BOLD))
@@ -24,6 +29,7 @@ const STYLES: Styles = Styles::styled()
     disable_version_flag = true,
     disable_help_flag = true,
 )]
+#[command(group(ArgGroup::new("deleting").args(["delete", "delete_empty"]).multiple(true)))]
 #[command(help_template = "{name} -- {about}\n\nVersion: {version}\
 \nAuthors: {author}\
 \nSource code: https://github.com/sharkLoc/findex.git\
@@ -37,9 +43,14 @@ pub struct Opt {
     #[arg(short = 'a', long = "all")]
     pub show_all: bool,
 
-    /// Set the maximum search depth. Defaults to unlimited depth (`usize::MAX`)
-    #[arg(short = 'd', long = "deepth", default_value_t = usize::MAX, value_name = "Number")]
-    pub deepth: usize,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub filter: FilterOpts,
+
+    #[command(flatten)]
+    pub ignore: IgnoreOpts,
 
     /// If specified, show file type in output
     #[arg(short = 't', long = "type")]
@@ -58,10 +69,6 @@ pub struct Opt {
     #[arg(short = 'c', long = "ctime")]
     pub created_time: bool,
 
-    /// If specified, show hidden files in output
-    #[arg(short = 'i', long = "hidden")]
-    pub show_hiden: bool,
-
     /// If specified, show full path in output
     #[arg(short = 'p', long = "full-path")]
     pub full_path: bool,
@@ -74,27 +81,270 @@ pub struct Opt {
     #[arg(short = 'D', long = "depth-first")]
     pub depth: bool,
 
+    /// Omit the header row in the output.
+    #[arg(short = 'H', long = "no-header")]
+    pub header: bool,
+
+    /// Show the raw and the fully resolved target of symlinks in output
+    #[arg(short = 'k', long = "link-target")]
+    pub link_target: bool,
+
+    /// Show the type, size and time of the link target instead of the symlink itself
+    #[arg(short = 'L', long = "dereference")]
+    pub dereference: bool,
+
+    /// Filter by file type: `f` (file), `d` (directory), `l` (symlink), `p` (fifo), `s` (socket),
+    /// `b` (block device), `c` (char device), `x` (executable file), `L` (broken symlink)
+    /// {n}Examples:
+    /// {n}     - Only files: `-T f`
+    /// {n}     - Only directories: `-T d`
+    /// {n}     - Pipes and sockets: `-T p,s`
+    /// {n}     - Dangling links: `-T L`
+    #[arg(
+        short = 'T',
+        long = "filter-type",
+        value_name = "String",
+        value_delimiter = ',',
+        hide_possible_values = true
+    )]
+    pub filetype: Vec<FileKind>,
+
+    /// Write the output to a file instead of stdout
+    #[arg(short = 'o', long = "out", value_name = "File")]
+    pub out: Option<String>,
+
+    /// Show checksum columns of regular files, accepts a comma separated list
+    /// {n}Supported: `sha256`, `blake3`, `xxh3`, `md5`. Digests are cached by inode, size and mtime
+    #[arg(long = "hash", value_enum, value_delimiter = ',', value_name = "Algo")]
+    pub hash: Vec<HashAlgo>,
+
+    /// Skip hashing files larger than the specified size (in bytes), shown as `-`
+    #[arg(long = "hash-max-size", value_name = "Number", requires = "hash")]
+    pub hash_max_size: Option<u64>,
+
+    /// Do not read or write the checksum cache `~/.cache/fdx/hash-cache.tsv`, which keeps the digests
+    /// of the 100000 most recently hashed files and is safe to delete
+    #[arg(long = "no-hash-cache", requires = "hash")]
+    pub no_hash_cache: bool,
+
+    /// Only show regular files whose content matches a regex, `-I` ignores case. Binary files are skipped
+    /// {n}Combines with the other filters, e.g. logs changed within the last day: `-e log --where 'mtime > -1d' --contains 'ERROR|FATAL'`
+    #[arg(long = "contains", value_name = "Regex")]
+    pub contains: Option<String>,
+
+    /// Skip reading files larger than the specified size (in bytes) with `--contains` and `--lines`,
+    /// {n}for compressed files the decompressed size counts as well. `--kind-mismatch` decodes
+    /// {n}compressed files up to this size. Defaults to 100 MiB
+    #[arg(long = "contains-max-size", value_name = "Number", default_value_t = 100 * 1024 * 1024, hide_default_value = true)]
+    pub contains_max_size: u64,
+
+    /// Show the number of lines of text files in output, `-` for directories and binary files
+    #[arg(long = "lines")]
+    pub lines: bool,
+
+    /// Show the content type detected from the magic bytes of regular files in output
+    #[arg(long = "show-kind")]
+    pub show_kind: bool,
+
+    /// Filter regular files by the content type detected from their magic bytes, accepts a comma separated list
+    /// {n}Supported: `elf`, `gzip`, `bzip2`, `xz`, `zstd`, `zip`, `pdf`, `png`, `jpeg`, `sqlite`, `hdf5`, `bam`, `cram`,
+    /// {n}`text` (also matches `utf8`), `utf8`, `binary`, `empty`
+    #[arg(
+        long = "kind",
+        value_enum,
+        value_delimiter = ',',
+        value_name = "Kind",
+        hide_possible_values = true
+    )]
+    pub kind: Vec<Kind>,
+
+    /// Only show files whose extension disagrees with their content, e.g. a `.gz` holding plain text,
+    /// {n}and compressed files that fail to decode. The reason is shown in a Mismatch column
+    #[arg(long = "kind-mismatch")]
+    pub kind_mismatch: bool,
+
+    /// Read `.gz`, `.bz2`, `.xz` and `.zst` files as they are with `--contains` and `--lines`.
+    /// {n}By default they are decompressed, detected by their magic bytes
+    #[arg(long = "no-decompress")]
+    pub no_decompress: bool,
+
+    /// Print the matching lines with their line numbers below each file with `--contains`
+    #[arg(long = "show-matches", requires = "contains")]
+    pub show_matches: bool,
+
+    /// Number of lines to print before and after each matching line, use with `--show-matches`
+    #[arg(
+        short = 'C',
+        long = "context",
+        value_name = "Number",
+        default_value_t = 0,
+        requires = "show_matches"
+    )]
+    pub context: usize,
+
+    /// Execute a command for each matched entry, commands run in parallel
+    /// {n}Placeholders: `{}` path, `{name}` file name, `{stem}` name without extension,
+    /// {n}`{parent}` parent directory, `{ext}` extension, `{group}` named capture group of `-r`. The path is appended if none is given
+    /// {n}Terminate the command with `;` if more options follow, e.g. `-x gzip -t {} \; -e gz`
+    #[arg(short = 'x', long = "exec", num_args = 1.., allow_hyphen_values = true, value_terminator = ";", value_name = "cmd", conflicts_with = "exec_batch")]
+    pub exec: Option<Vec<String>>,
+
+    /// Execute a command once with as many matched entries as the argument limit allows
+    /// {n}Accepts the same placeholders as `-x`, every argument with a placeholder is repeated per entry
+    #[arg(short = 'X', long = "exec-batch", num_args = 1.., allow_hyphen_values = true, value_terminator = ";", value_name = "cmd")]
+    pub exec_batch: Option<Vec<String>>,
+
+    /// Number of parallel jobs for `-x`, `-X`, `--hash`, `--contains`, `--lines` and `--kind`. Defaults to the number of CPU cores
+    #[arg(short = 'j', long = "jobs", value_name = "Number")]
+    pub jobs: Option<usize>,
+
+    /// Delete matched files, a preview with the total size is shown and confirmation is required
+    #[arg(long = "delete", conflicts_with_all = ["exec", "exec_batch"])]
+    pub delete: bool,
+
+    /// Also remove matched empty directories with `--delete`, bottom-up in depth-first order
+    #[arg(long = "delete-dirs", requires = "delete")]
+    pub delete_dirs: bool,
+
+    /// Delete zero-byte files and directories that hold nothing but empty directories, bottom-up
+    /// so that every directory is already empty when it is removed
+    /// {n}Same as `--empty --empty-dirs-recursive --delete --delete-dirs`
+    #[arg(long = "delete-empty", conflicts_with_all = ["exec", "exec_batch"])]
+    pub delete_empty: bool,
+
+    /// Only show what `--delete` would remove and the total size to be freed
+    #[arg(long = "dry-run", requires = "deleting")]
+    pub dry_run: bool,
+
+    /// Do not ask for confirmation before deleting
+    #[arg(short = 'y', long = "yes", requires = "deleting")]
+    pub yes: bool,
+
+    /// Move items to the freedesktop.org trash instead of unlinking them with `--delete`
+    #[arg(long = "trash", requires = "deleting")]
+    pub trash: bool,
+
+    /// Append the deleted paths to an audit log file with `--delete`
+    #[arg(long = "audit-log", value_name = "File", requires = "deleting")]
+    pub audit_log: Option<String>,
+
+    /// Copy matched entries into a directory, keeping their path relative to the search root
+    #[arg(long = "copy-to", value_name = "Dir", conflicts_with_all = ["exec", "exec_batch", "deleting", "move_to"])]
+    pub copy_to: Option<String>,
+
+    /// Move matched entries into a directory, keeping their path relative to the search root
+    /// {n}Falls back to copy and remove when the destination is on another filesystem
+    #[arg(long = "move-to", value_name = "Dir", conflicts_with_all = ["exec", "exec_batch", "deleting"])]
+    pub move_to: Option<String>,
+
+    /// What to do when a destination file already exists with `--copy-to` or `--move-to`
+    #[arg(long = "conflict", value_enum, default_value_t = Conflict::Skip, value_name = "Policy")]
+    pub conflict: Conflict,
+
+    /// Write matched entries into an archive with paths relative to the search root
+    /// {n}The format is inferred from the extension: `.tar`, `.tar.gz`/`.tgz`, `.tar.zst`, `.zip`
+    #[arg(long = "archive", value_name = "File", conflicts_with_all = ["exec", "exec_batch", "deleting", "copy_to", "move_to"])]
+    pub archive: Option<String>,
+
+    /// Output format of the matched entries. `csv` and `json` write the path and the named capture
+    /// groups of `-r` per entry, e.g. a sample sheet with `-r '(?P<sample>[^_]+)_S\d+_(?P<lane>L\d+)' --format csv`
+    #[arg(long = "format", value_enum, default_value_t = OutFormat::Table, value_name = "Format", conflicts_with_all = ["exec", "exec_batch", "deleting", "copy_to", "move_to", "archive"])]
+    pub format: OutFormat,
+
+    /// Summarize matched entries per value of a named capture group of `-r`: entries and total size
+    /// {n}Takes `--format` as well, e.g. `-r '(?P<sample>[^_]+)_S\d+' --group-by sample --format json`
+    #[arg(long = "group-by", value_name = "Group", conflicts_with_all = ["exec", "exec_batch", "deleting", "copy_to", "move_to", "archive"])]
+    pub group_by: Option<String>,
+
+    /// Control verbosity of logging
+    #[arg(short = 'v', long = "verbosity", action = ArgAction::Count, global = true, default_value_t = 4)]
+    pub verbose: u8,
+
+    /// Prints help information
+    #[arg(short = 'h', long, action = ArgAction::Help)]
+    pub help: Option<String>,
+
+    /// Prints version information
+    #[arg(short = 'V', long, action = ArgAction::Version)]
+    pub version: Option<String>,
+}
+
+/// Options shared by the listing and the sub commands to select entries
+#[derive(Args, Debug)]
+pub struct FilterOpts {
+    /// Set the maximum search depth. Defaults to unlimited depth (`usize::MAX`)
+    #[arg(short = 'd', long = "deepth", default_value_t = usize::MAX, value_name = "Number")]
+    pub deepth: usize,
+
+    /// If specified, show hidden files in output
+    #[arg(short = 'i', long = "hidden")]
+    pub show_hiden: bool,
+
     /// If specified, show sub-item in symbolink dir
     #[arg(short = 'l', long = "link")]
     pub show_link_dir: bool,
 
-    /// Filter files by extension (e.g., `gz`, `csv`, `txt`). Do not include the dot (`.`) in the extension
-    #[arg(short = 'e', long = "ext", value_name = "String")]
-    pub ext: Option<String>,
+    /// Filter files by extension (e.g., `gz`, `csv`, `txt`), ignoring case. Comma separated or repeated
+    /// {n}Multi-part extensions like `fastq.gz` are supported, as are the groups `@archive`,
+    /// `@image`, `@document`, `@text` and `@source`
+    /// {n}Examples:
+    /// {n}     - Tables: `-e csv,tsv`
+    /// {n}     - Compressed reads: `-e fastq.gz -e fq.gz`
+    #[arg(
+        short = 'e',
+        long = "ext",
+        value_name = "String",
+        value_delimiter = ','
+    )]
+    pub ext: Vec<String>,
+
+    /// Exclude files by extension, takes the same values as `-e`
+    #[arg(long = "exclude-ext", value_name = "String", value_delimiter = ',')]
+    pub exclude_ext: Vec<String>,
 
-    /// Apply a regular expression filter to file paths
-    /// {n}The regex is matched against the full file path (not just the file name)
+    /// Apply a regular expression filter to file paths, can be repeated
+    /// {n}The regex is matched against the path relative to the search root, see `--match-on`
+    /// {n}Note: it used to be matched against the path as walked, e.g. `./data/a.gz`. Anchored patterns
+    /// {n}like `-r '^\./data'` no longer match, write `-r '^data/'` or use `--match-on abspath`
+    /// {n}Named groups like `(?P<sample>[^_]+)` become extra output columns, `--format csv`/`json` fields,
+    /// {n}`--group-by` keys and `{sample}` placeholders in `-x`/`-X`
     /// {n}For example:
     /// {n}     - To match files ending with `.gz`: `-r "\.gz$"`
     /// {n}     - To match files containing "log" in their path: `-r "log"`
     /// {n}     Supports standard regex syntax. Use with `-I` to ignore case sensitivity
     #[arg(short = 'r', long = "regex", value_name = "Regex")]
-    pub regex: Option<String>,
+    pub regex: Vec<String>,
+
+    /// Require all `-r` patterns to match instead of any of them
+    #[arg(long = "all-regex")]
+    pub all_regex: bool,
+
+    /// Exclude entries matching a regular expression, can be repeated
+    #[arg(long = "not-regex", value_name = "Regex")]
+    pub not_regex: Vec<String>,
+
+    /// What `-r` and `--not-regex` are matched against
+    #[arg(long = "match-on", value_enum, default_value_t = MatchOn::Relpath, value_name = "Target")]
+    pub match_on: MatchOn,
 
     /// Ignore case when filtering with the `-r` regex option
     #[arg(short = 'I', long = "ignore-case")]
     pub ignore_case: bool,
 
+    /// Filter entries with a boolean expression, combined with the other filters
+    /// {n}Attributes: `name`, `path`, `ext`, `type`, `depth`, `size`, `mode` (octal), `owner` and
+    /// `group` (name or id), `mtime`, `atime`, `ctime` (created), `sha256`, `blake3`, `xxh3`, `md5`
+    /// {n}Operators: `==` `!=` `<` `<=` `>` `>=` `~` (regex) `!~` `in [..]`, `and` `or` `not` and parentheses
+    /// {n}Sizes take `k`, `M`, `G`, `T` units. Times are points in time, either before now like `-30d`, `-12h`,
+    /// {n}`-2w` or dates like `2024-01-31`: `mtime < -30d` is modified more than 30 days ago, `mtime > -1d` within
+    /// {n}the last day and `mtime < 2024-01-31` before that date
+    /// {n}Types take the `-T` kinds, `type == file` matches executables as well
+    /// {n}Examples:
+    /// {n}     - `--where "(ext in [bam, cram] and size > 1G) or (name ~ 'tmp' and mtime < -30d)"`
+    /// {n}     - `--where "type == f and not mode == 644"`
+    #[arg(long = "where", value_name = "Expr")]
+    pub where_expr: Option<String>,
+
     /// Filter file size larger than the specified size (in bytes)
     /// {n}Examples:
     /// {n}     - Exclude files larger than 1 MB: `--max-size 1048576`
@@ -109,30 +359,366 @@ pub struct Opt {
     #[arg(long = "min-size", value_name = "Number")]
     pub file_size_min: Option<u64>,
 
-    /// Omit the header row in the output.
+    /// Only show entries matching a glob pattern, can be repeated
+    /// {n}A pattern with a `/` is matched against the path relative to the search root,
+    /// otherwise against the file name. `**` matches any number of directories
+    /// {n}Examples:
+    /// {n}     - Rust files: `-g "*.rs"`
+    /// {n}     - Markdown files under docs: `-g "docs/**/*.md"`
+    #[arg(short = 'g', long = "glob", value_name = "Glob")]
+    pub glob: Vec<String>,
+
+    /// Exclude entries matching a glob pattern, excluded directories are not descended into.
+    /// Can be repeated
+    #[arg(long = "exclude", value_name = "Glob")]
+    pub exclude: Vec<String>,
+
+    /// Skip descending into directories with this name, can be repeated
+    /// {n}Examples: `--prune node_modules --prune .snakemake --prune work`
+    #[arg(long = "prune", value_name = "Name")]
+    pub prune: Vec<String>,
+
+    /// Only show entries owned by a user name or uid
+    #[arg(long = "owner", value_name = "User")]
+    pub owner: Option<String>,
+
+    /// Only show entries belonging to a group name or gid
+    #[arg(long = "group", value_name = "Group")]
+    pub group: Option<String>,
+
+    /// Only show entries whose uid has no user in `/etc/passwd`
+    #[arg(long = "nouser")]
+    pub nouser: bool,
+
+    /// Only show entries whose gid has no group in `/etc/group`
+    #[arg(long = "nogroup")]
+    pub nogroup: bool,
+
+    /// Only show zero-byte regular files and directories without any entries
+    #[arg(long = "empty")]
+    pub empty: bool,
+
+    /// Only show directories that contain nothing but empty directories, at any depth
+    #[arg(long = "empty-dirs-recursive")]
+    pub empty_dirs_recursive: bool,
+
+    /// Filter by permission bits, octal or symbolic
+    /// {n}Examples:
+    /// {n}     - Exactly `rw-r--r--`: `--perm 644` or `--perm u=rw,go=r`
+    /// {n}     - All of the bits set, e.g. executable by the owner and writable by others: `--perm -u+x,o+w`
+    /// {n}     - Any of the bits set, e.g. writable by group or others: `--perm /022`
+    #[arg(long = "perm", value_name = "Mode", allow_hyphen_values = true)]
+    pub perm: Option<String>,
+}
+
+/// Ignore files of the listing and `rename`. `dupes` and `manifest` see every file, a checksum
+/// manifest must not silently leave out ignored build outputs or logs
+#[derive(Args, Debug)]
+pub struct IgnoreOpts {
+    /// Do not respect `.gitignore`, `.ignore`, `.git/info/exclude` and global ignore files
+    #[arg(long = "no-ignore")]
+    pub no_ignore: bool,
+
+    /// Read additional ignore patterns from a file with gitignore syntax, can be repeated
+    #[arg(
+        long = "ignore-file",
+        value_name = "File",
+        conflicts_with = "no_ignore"
+    )]
+    pub ignore_file: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Find duplicate files by size and content hash
+    Dupes(DupesOpt),
+    /// Create or verify a manifest of file sizes, mtimes and checksums
+    Manifest(ManifestOpt),
+    /// Compute a Merkle-style hash for each directory from its contents
+    Fingerprint(FingerprintOpt),
+    /// Compare two directory trees and report the entries that differ
+    Compare(CompareOpt),
+    /// Rename matched entries with a regex replacement, with preview and undo
+    Rename(RenameOpt),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct DupesOpt {
+    /// The root directory path to be searched, default "."
+    #[arg(value_name = "path")]
+    pub rootdir: Option<String>,
+
+    #[command(flatten)]
+    pub filter: FilterOpts,
+
+    /// Display file size in a human-readable format: `k` (KB), `m` (MB), `g` (GB). Defaults to `b` (bytes)
+    #[arg(short = 'b', long = "byte", default_value_t = String::from("b"), value_name = "String")]
+    pub size_fmt: String,
+
+    /// Replace duplicates with hard links to the oldest file of each set on the same filesystem
+    #[arg(long = "hardlink", conflicts_with = "reflink")]
+    pub hardlink: bool,
+
+    /// Replace duplicates with reflinks (`FICLONE`) of the oldest file, if the filesystem supports it
+    #[arg(long = "reflink")]
+    pub reflink: bool,
+
+    /// Only report the bytes that `--hardlink` or `--reflink` would save
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Do not ask for confirmation before replacing duplicates
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// Output format of the duplicate sets
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutFormat::Table)]
+    pub format: OutFormat,
+
+    /// Omit the header row in the output
     #[arg(short = 'H', long = "no-header")]
     pub header: bool,
 
-    /// Filter by file type: `f` (file), `d` (directory), `l` (symlink)
-    /// {n}Examples:
-    /// {n}     - Only files: `-T f`
-    /// {n}     - Only directories: `-T d`
-    #[arg(short = 'T', long = "filter-type", value_name = "String")]
-    pub filetype: Option<String>,
+    /// Write the output to a file instead of stdout
+    #[arg(short = 'o', long = "out", value_name = "File")]
+    pub out: Option<String>,
+
+    /// Prints help information
+    #[arg(short = 'h', long, action = ArgAction::Help)]
+    pub help: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ManifestOpt {
+    #[command(subcommand)]
+    pub command: ManifestCommand,
+
+    /// Prints help information
+    #[arg(short = 'h', long, action = ArgAction::Help)]
+    pub help: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ManifestCommand {
+    /// Write the path, size, mtime and checksum of every matched file
+    Create(ManifestCreateOpt),
+    /// Report missing, extra and modified files compared with a manifest
+    Verify(ManifestVerifyOpt),
+}
+
+#[derive(Args, Debug)]
+pub struct ManifestCreateOpt {
+    /// The root directory path to be searched, default "."
+    #[arg(value_name = "path")]
+    pub rootdir: Option<String>,
+
+    #[command(flatten)]
+    pub filter: FilterOpts,
+
+    /// Checksum algorithm
+    #[arg(long = "hash", value_enum, default_value_t = HashAlgo::Sha256, value_name = "Algo")]
+    pub hash: HashAlgo,
+
+    /// Manifest format, `sum` can also be checked with `sha256sum -c` in the root directory
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = ManifestFormat::Fdx)]
+    pub format: ManifestFormat,
+
+    /// Number of files hashed in parallel. Defaults to the number of CPU cores
+    #[arg(short = 'j', long = "jobs", value_name = "Number")]
+    pub jobs: Option<usize>,
+
+    /// Do not read or write the checksum cache `~/.cache/fdx/hash-cache.tsv`, which keeps the digests
+    /// of the 100000 most recently hashed files and is safe to delete
+    #[arg(long = "no-hash-cache")]
+    pub no_hash_cache: bool,
+
+    /// Write the manifest to a file instead of stdout
+    #[arg(short = 'o', long = "out", value_name = "File")]
+    pub out: Option<String>,
+
+    /// Prints help information
+    #[arg(short = 'h', long, action = ArgAction::Help)]
+    pub help: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ManifestVerifyOpt {
+    /// The manifest file to verify
+    #[arg(value_name = "MANIFEST")]
+    pub manifest: String,
+
+    /// Root directory of the paths in the manifest. Defaults to the directory of the manifest
+    #[arg(short = 'C', long = "root", value_name = "Dir")]
+    pub root: Option<String>,
+
+    #[command(flatten)]
+    pub filter: FilterOpts,
+
+    /// Checksum algorithm of a `sum` manifest, `fdx` manifests record their own
+    #[arg(long = "hash", value_enum, default_value_t = HashAlgo::Sha256, value_name = "Algo")]
+    pub hash: HashAlgo,
+
+    /// Number of files hashed in parallel. Defaults to the number of CPU cores
+    #[arg(short = 'j', long = "jobs", value_name = "Number")]
+    pub jobs: Option<usize>,
+
+    /// Do not read or write the checksum cache `~/.cache/fdx/hash-cache.tsv`, which keeps the digests
+    /// of the 100000 most recently hashed files and is safe to delete
+    #[arg(long = "no-hash-cache")]
+    pub no_hash_cache: bool,
+
+    /// Prints help information
+    #[arg(short = 'h', long, action = ArgAction::Help)]
+    pub help: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct FingerprintOpt {
+    /// The root directory path to be fingerprinted, default "."
+    #[arg(value_name = "path")]
+    pub rootdir: Option<String>,
+
+    /// Print the hashes of directories down to this depth, the root directory is depth 0
+    #[arg(
+        short = 'd',
+        long = "depth",
+        default_value_t = 0,
+        value_name = "Number"
+    )]
+    pub depth: usize,
+
+    /// Include hidden files and directories
+    #[arg(short = 'i', long = "hidden")]
+    pub show_hiden: bool,
+
+    /// Number of files hashed in parallel. Defaults to the number of CPU cores
+    #[arg(short = 'j', long = "jobs", value_name = "Number")]
+    pub jobs: Option<usize>,
+
+    /// Do not read or write the checksum cache `~/.cache/fdx/hash-cache.tsv`, which keeps the digests
+    /// of the 100000 most recently hashed files and is safe to delete
+    #[arg(long = "no-hash-cache")]
+    pub no_hash_cache: bool,
+
+    /// Omit the header row in the output
+    #[arg(short = 'H', long = "no-header")]
+    pub header: bool,
 
     /// Write the output to a file instead of stdout
     #[arg(short = 'o', long = "out", value_name = "File")]
     pub out: Option<String>,
 
-    /// Control verbosity of logging
-    #[arg(short = 'v', long = "verbosity", action = ArgAction::Count, global = true, default_value_t = 4)]
-    pub verbose: u8,
+    /// Prints help information
+    #[arg(short = 'h', long, action = ArgAction::Help)]
+    pub help: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct CompareOpt {
+    /// The left directory
+    #[arg(value_name = "left")]
+    pub left: String,
+
+    /// The right directory
+    #[arg(value_name = "right")]
+    pub right: String,
+
+    /// Also compare the content of regular files with the same size, byte by byte
+    #[arg(short = 'c', long = "content")]
+    pub content: bool,
+
+    /// Do not report entries that only differ in mtime
+    #[arg(long = "ignore-mtime")]
+    pub ignore_mtime: bool,
+
+    /// Do not report entries that only differ in permission bits
+    #[arg(long = "ignore-mode")]
+    pub ignore_mode: bool,
+
+    /// Include hidden files and directories
+    #[arg(short = 'i', long = "hidden")]
+    pub show_hiden: bool,
+
+    /// Omit the header row in the output
+    #[arg(short = 'H', long = "no-header")]
+    pub header: bool,
+
+    /// Write the output to a file instead of stdout
+    #[arg(short = 'o', long = "out", value_name = "File")]
+    pub out: Option<String>,
 
     /// Prints help information
     #[arg(short = 'h', long, action = ArgAction::Help)]
     pub help: Option<String>,
+}
 
-    /// Prints version information
-    #[arg(short = 'V', long, action = ArgAction::Version)]
-    pub version: Option<String>,
+#[derive(Args, Debug)]
+pub struct RenameOpt {
+    /// The root directory path to be searched, default "."
+    #[arg(value_name = "path")]
+    pub rootdir: Option<String>,
+
+    #[command(flatten)]
+    pub filter: FilterOpts,
+
+    #[command(flatten)]
+    pub ignore: IgnoreOpts,
+
+    /// Only rename entries of these types, takes the same values as `-T` of the listing
+    #[arg(
+        short = 'T',
+        long = "filter-type",
+        value_name = "String",
+        value_delimiter = ',',
+        hide_possible_values = true
+    )]
+    pub filetype: Vec<FileKind>,
+
+    /// Regex matched against the file name, entries that do not match are left alone
+    #[arg(long = "from", value_name = "Regex", required_unless_present = "undo")]
+    pub from: Option<String>,
+
+    /// Replacement for the matched part of the name, `$1` or `${name}` insert capture groups
+    /// {n}Example: `fdx rename --from '^(?P<s>[^_]+)_S\d+_' --to '${s}_'`
+    #[arg(
+        long = "to",
+        value_name = "Replacement",
+        required_unless_present = "undo"
+    )]
+    pub to: Option<String>,
+
+    /// Replace every match in the name instead of only the first one
+    #[arg(long = "replace-all")]
+    pub replace_all: bool,
+
+    /// Only show the renames without changing anything
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Do not ask for confirmation before renaming
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// Where to write the undo journal. Defaults to `.fdx-rename-<time>.journal` in the root directory
+    #[arg(long = "journal", value_name = "File")]
+    pub journal: Option<String>,
+
+    /// Revert the renames recorded in a journal
+    #[arg(long = "undo", value_name = "File", conflicts_with_all = ["from", "to"])]
+    pub undo: Option<String>,
+
+    /// Omit the header row in the preview
+    #[arg(short = 'H', long = "no-header")]
+    pub header: bool,
+
+    /// Prints help information
+    #[arg(short = 'h', long, action = ArgAction::Help)]
+    pub help: Option<String>,
 }
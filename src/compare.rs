@@ -0,0 +1,224 @@
+use log::info;
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    io::{self, BufWriter, Error, Write},
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+};
+use tabwriter::TabWriter;
+use walkdir::{DirEntry, WalkDir};
+
+use crate::{cli::CompareOpt, dupes::same_content, process::is_hidden};
+
+fn kind(entry: &DirEntry) -> &'static str {
+    if entry.file_type().is_dir() {
+        "dir"
+    } else if entry.file_type().is_file() {
+        "file"
+    } else if entry.file_type().is_symlink() {
+        "symlink"
+    } else {
+        "other"
+    }
+}
+
+// attributes that differ between two entries with the same relative path
+fn differences(
+    left: &DirEntry,
+    right: &DirEntry,
+    opt: &CompareOpt,
+) -> Result<Vec<&'static str>, Error> {
+    if kind(left) != kind(right) {
+        return Ok(vec!["type"]);
+    }
+    let (lmeta, rmeta) = (left.metadata()?, right.metadata()?);
+    let mut diff = vec![];
+    let is_file = left.file_type().is_file();
+
+    if is_file && lmeta.len() != rmeta.len() {
+        diff.push("size");
+    }
+    if !opt.ignore_mtime && !left.file_type().is_dir() && lmeta.mtime() != rmeta.mtime() {
+        diff.push("mtime");
+    }
+    if !opt.ignore_mode && lmeta.permissions().mode() != rmeta.permissions().mode() {
+        diff.push("mode");
+    }
+    if left.file_type().is_symlink() && fs::read_link(left.path())? != fs::read_link(right.path())?
+    {
+        diff.push("target");
+    }
+    // content is only compared if the sizes match
+    if opt.content
+        && is_file
+        && !diff.contains(&"size")
+        && !same_content(left.path(), right.path())?
+    {
+        diff.push("content");
+    }
+    Ok(diff)
+}
+
+/// Walk both trees in the same name-sorted order and report the entries that differ.
+/// Returns true if the trees are equal
+pub fn compare(opt: &CompareOpt) -> Result<bool, Error> {
+    let (left_root, right_root) = (Path::new(&opt.left), Path::new(&opt.right));
+    for root in [left_root, right_root] {
+        if !root.is_dir() {
+            eprintln!("error: dir `{}` not exists", root.display());
+            return Ok(false);
+        }
+    }
+    let walk = |root: &Path| {
+        WalkDir::new(root)
+            .min_depth(1)
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+            .into_iter()
+    };
+    let (mut left_iter, mut right_iter) = (walk(left_root), walk(right_root));
+
+    let mut fp: Box<dyn Write> = if let Some(out) = opt.out.as_ref() {
+        Box::new(BufWriter::new(File::create(out)?))
+    } else {
+        Box::new(TabWriter::new(BufWriter::new(io::stdout())))
+    };
+    if !opt.header {
+        writeln!(fp, "Status\tDiffer\tPath")?;
+    }
+
+    let next = |iter: &mut walkdir::IntoIter| -> Result<Option<DirEntry>, Error> {
+        while let Some(entry) = iter.next() {
+            let entry = entry?;
+            if !is_hidden(&entry) || opt.show_hiden {
+                return Ok(Some(entry));
+            }
+            if entry.file_type().is_dir() {
+                iter.skip_current_dir();
+            }
+        }
+        Ok(None)
+    };
+
+    let (mut only_left, mut only_right, mut differ, mut same) = (0usize, 0usize, 0usize, 0usize);
+    let (mut left, mut right) = (next(&mut left_iter)?, next(&mut right_iter)?);
+    loop {
+        // a pre-order walk with sorted children yields paths in component order
+        let order = match (&left, &right) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(l), Some(r)) => l
+                .path()
+                .strip_prefix(left_root)
+                .unwrap()
+                .cmp(r.path().strip_prefix(right_root).unwrap()),
+        };
+
+        match order {
+            Ordering::Less => {
+                let l = left.take().unwrap();
+                let rel = l.path().strip_prefix(left_root).unwrap();
+                writeln!(fp, "only-left\t-\t{}", rel.display())?;
+                only_left += 1;
+                // the contents of a missing dir are not listed one by one
+                if l.file_type().is_dir() {
+                    left_iter.skip_current_dir();
+                }
+                left = next(&mut left_iter)?;
+            }
+            Ordering::Greater => {
+                let r = right.take().unwrap();
+                let rel = r.path().strip_prefix(right_root).unwrap();
+                writeln!(fp, "only-right\t-\t{}", rel.display())?;
+                only_right += 1;
+                if r.file_type().is_dir() {
+                    right_iter.skip_current_dir();
+                }
+                right = next(&mut right_iter)?;
+            }
+            Ordering::Equal => {
+                let (l, r) = (left.take().unwrap(), right.take().unwrap());
+                let diff = differences(&l, &r, opt)?;
+                if diff.is_empty() {
+                    same += 1;
+                } else {
+                    let rel = l.path().strip_prefix(left_root).unwrap();
+                    writeln!(fp, "differ\t{}\t{}", diff.join(","), rel.display())?;
+                    differ += 1;
+                    // a file on one side and a dir on the other, the dir contents are not compared
+                    if diff == ["type"] {
+                        if l.file_type().is_dir() {
+                            left_iter.skip_current_dir();
+                        }
+                        if r.file_type().is_dir() {
+                            right_iter.skip_current_dir();
+                        }
+                    }
+                }
+                left = next(&mut left_iter)?;
+                right = next(&mut right_iter)?;
+            }
+        }
+    }
+    fp.flush()?;
+
+    info!(
+        "same: {}, differ: {}, only in left: {}, only in right: {}",
+        same, differ, only_left, only_right
+    );
+    Ok(differ + only_left + only_right == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_compare() {
+        let base = TempDir::new("compare");
+        let (left, right) = (base.join("left"), base.join("right"));
+        fs::create_dir_all(left.join("kind")).unwrap();
+        fs::create_dir_all(&right).unwrap();
+        fs::write(left.join("kind").join("inner"), b"x").unwrap();
+        fs::write(right.join("kind"), b"x").unwrap();
+        fs::write(left.join("only-l.txt"), b"l").unwrap();
+        fs::write(right.join("only-r.txt"), b"r").unwrap();
+        for (root, data) in [(&left, b"abc"), (&right, b"abd")] {
+            fs::write(root.join("data.txt"), data).unwrap();
+            fs::write(root.join("same.txt"), b"same").unwrap();
+        }
+
+        let out = base.join("out.tsv");
+        let opt = CompareOpt {
+            left: left.to_string_lossy().into_owned(),
+            right: right.to_string_lossy().into_owned(),
+            content: true,
+            ignore_mtime: true,
+            ignore_mode: true,
+            show_hiden: false,
+            header: true,
+            out: Some(out.to_string_lossy().into_owned()),
+            help: None,
+        };
+        assert!(!compare(&opt).unwrap());
+        let report = fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            report,
+            "differ\tcontent\tdata.txt\n\
+             differ\ttype\tkind\n\
+             only-left\t-\tonly-l.txt\n\
+             only-right\t-\tonly-r.txt\n"
+        );
+
+        // without the content check the files are equal, the sizes match
+        let opt = CompareOpt {
+            content: false,
+            ..opt
+        };
+        compare(&opt).unwrap();
+        let report = fs::read_to_string(&out).unwrap();
+        assert!(!report.contains("data.txt"));
+    }
+}
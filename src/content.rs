@@ -0,0 +1,382 @@
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use log::{debug, warn};
+use memmap2::Mmap;
+use regex::bytes::{Regex, RegexBuilder};
+use std::{
+    fs::{self, File, Metadata},
+    io::{Error, ErrorKind, Read, Seek, SeekFrom},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+use xz2::read::XzDecoder;
+
+// files of at least this size are memory-mapped instead of read into memory
+const MMAP_MIN_SIZE: u64 = 1024 * 1024;
+// a NUL byte in the first block marks a file as binary, as grep does
+const BINARY_PROBE: usize = 8 * 1024;
+
+/// A line printed with `--show-matches`, either a matching line or one of its context lines
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub number: usize,
+    pub text: String,
+    pub is_match: bool,
+}
+
+/// Compressed formats that are read transparently, detected by magic bytes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Codec {
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Codec::Gzip)
+        } else if head.starts_with(b"BZh") {
+            Some(Codec::Bzip2)
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Codec::Xz)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Codec::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Concatenated streams are decoded as one, as zcat does
+    pub fn decoder(&self, file: File) -> Result<Box<dyn Read>, Error> {
+        Ok(match self {
+            Codec::Gzip => Box::new(MultiGzDecoder::new(file)),
+            Codec::Bzip2 => Box::new(MultiBzDecoder::new(file)),
+            Codec::Xz => Box::new(XzDecoder::new_multi_decoder(file)),
+            Codec::Zstd => Box::new(zstd::Decoder::new(file)?),
+        })
+    }
+}
+
+// contents of a file, mapped or read
+enum Data {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Data {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Data::Mapped(map) => map,
+            Data::Read(buf) => buf,
+        }
+    }
+}
+
+/// How `--contains` and `--lines` read files, in `jobs` parallel threads
+#[derive(Clone, Copy)]
+pub struct Reader {
+    max_size: u64,
+    decompress: bool,
+    jobs: usize,
+}
+
+impl Reader {
+    pub fn new(max_size: u64, decompress: bool, jobs: usize) -> Self {
+        Reader {
+            max_size,
+            decompress,
+            jobs,
+        }
+    }
+
+    // None for files that are skipped: not regular, too large or binary. Compressed files are
+    // decoded, their decoded size is limited as well
+    fn read(&self, path: &Path) -> Result<Option<Data>, Error> {
+        let Some(meta) = regular_file(path)? else {
+            return Ok(None);
+        };
+        if meta.len() > self.max_size {
+            debug!("skip reading large file `{}`", path.display());
+            return Ok(None);
+        }
+
+        let mut file = File::open(path)?;
+        let mut codec = None;
+        if self.decompress {
+            let mut head = Vec::with_capacity(6);
+            file.by_ref().take(6).read_to_end(&mut head)?;
+            file.seek(SeekFrom::Start(0))?;
+            codec = Codec::detect(&head);
+        }
+        let data = if let Some(codec) = codec {
+            let mut buf = vec![];
+            codec
+                .decoder(file)?
+                .take(self.max_size.saturating_add(1))
+                .read_to_end(&mut buf)
+                .map_err(|e| Error::new(e.kind(), format!("{:?} stream: {}", codec, e)))?;
+            if buf.len() as u64 > self.max_size {
+                debug!("skip reading large decompressed file `{}`", path.display());
+                return Ok(None);
+            }
+            Data::Read(buf)
+        } else if meta.len() >= MMAP_MIN_SIZE {
+            // SAFETY: the map is only read, a file truncated meanwhile by another process is
+            // the same hazard grep and ripgrep accept
+            match unsafe { Mmap::map(&file) } {
+                Ok(map) => Data::Mapped(map),
+                Err(e) => {
+                    debug!("failed to map `{}`, reading it: {}", path.display(), e);
+                    read_all(file, meta.len())?
+                }
+            }
+        } else {
+            read_all(file, meta.len())?
+        };
+        if data[..data.len().min(BINARY_PROBE)].contains(&0) {
+            debug!("skip binary file `{}`", path.display());
+            return Ok(None);
+        }
+        Ok(Some(data))
+    }
+
+    /// Number of lines of a text file, `-` if the file is skipped or can not be read
+    pub fn line_count(&self, path: &Path) -> String {
+        match self.read(path) {
+            Ok(Some(data)) => count_lines(&data).to_string(),
+            Ok(None) => "-".to_string(),
+            Err(e) => {
+                warn!("failed to read `{}`: {}", path.display(), e);
+                "-".to_string()
+            }
+        }
+    }
+
+    /// Count lines in parallel, results are in the same order as `paths`
+    pub fn line_counts(&self, paths: &[PathBuf]) -> Vec<String> {
+        par_map(paths, self.jobs, |path| self.line_count(path))
+    }
+}
+
+/// The metadata of a regular file or of the file a link points to, None for anything else,
+/// dangling links included. The type is checked before opening, opening a fifo without a writer
+/// blocks
+pub fn regular_file(path: &Path) -> Result<Option<Metadata>, Error> {
+    match fs::metadata(path) {
+        Ok(meta) => Ok(meta.is_file().then_some(meta)),
+        Err(_) if path.is_symlink() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_all(mut file: File, len: u64) -> Result<Data, Error> {
+    let mut buf = Vec::with_capacity(len as usize);
+    file.read_to_end(&mut buf)?;
+    Ok(Data::Read(buf))
+}
+
+// a last line without a trailing newline counts as well, unlike `wc -l`
+fn count_lines(data: &[u8]) -> usize {
+    let newlines = data.iter().filter(|b| **b == b'\n').count();
+    if data.is_empty() || data.ends_with(b"\n") {
+        newlines
+    } else {
+        newlines + 1
+    }
+}
+
+/// Run `f` for each item in `jobs` threads, results are in the same order as `items`
+pub fn par_map<I, T, F>(items: &[I], jobs: usize, f: F) -> Vec<T>
+where
+    I: Sync,
+    T: Clone + Default + Send,
+    F: Fn(&I) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![T::default(); items.len()]);
+    thread::scope(|s| {
+        for _ in 0..jobs.max(1).min(items.len().max(1)) {
+            s.spawn(|| {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(idx) else {
+                        break;
+                    };
+                    let value = f(item);
+                    results.lock().unwrap()[idx] = value;
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+/// `--contains`, filters regular files by their content
+pub struct ContentFilter {
+    regex: Regex,
+    reader: Reader,
+    show_matches: bool,
+    context: usize,
+}
+
+impl ContentFilter {
+    pub fn new(
+        pattern: &str,
+        ignore_case: bool,
+        reader: Reader,
+        show_matches: bool,
+        context: usize,
+    ) -> Result<Self, Error> {
+        // `^` and `$` match at line boundaries, as they do in grep
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .multi_line(true)
+            .build()
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid regex `{}`: {}", pattern, e),
+                )
+            })?;
+        Ok(ContentFilter {
+            regex,
+            reader,
+            show_matches,
+            context,
+        })
+    }
+
+    /// None if the file does not match or is skipped, otherwise the lines to print, which are
+    /// only collected with `--show-matches`
+    pub fn search(&self, path: &Path) -> Option<Vec<Line>> {
+        let data = match self.reader.read(path) {
+            Ok(data) => data?,
+            Err(e) => {
+                warn!("failed to read `{}`: {}", path.display(), e);
+                return None;
+            }
+        };
+        if !self.show_matches {
+            return self.regex.is_match(&data).then(Vec::new);
+        }
+        let lines = self.matching_lines(&data);
+        (!lines.is_empty()).then_some(lines)
+    }
+
+    // lines with a match starting in them, merged with `context` lines around each
+    fn matching_lines(&self, data: &[u8]) -> Vec<Line> {
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(
+                data.iter()
+                    .enumerate()
+                    .filter(|(_, b)| **b == b'\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        // a trailing newline does not start another line
+        let count = if data.ends_with(b"\n") {
+            starts.len() - 1
+        } else {
+            starts.len()
+        };
+
+        let mut hits: Vec<usize> = vec![];
+        for m in self.regex.find_iter(data) {
+            let line = starts.partition_point(|s| *s <= m.start()) - 1;
+            if line < count && hits.last() != Some(&line) {
+                hits.push(line);
+            }
+        }
+
+        let text = |n: usize| {
+            let end = starts.get(n + 1).map_or(data.len(), |e| e - 1);
+            String::from_utf8_lossy(&data[starts[n]..end])
+                .trim_end_matches('\r')
+                .replace('\t', " ")
+        };
+        let mut lines = vec![];
+        // the first line not printed yet, so that overlapping context is printed once
+        let mut next = 0usize;
+        for hit in hits.iter() {
+            let from = hit.saturating_sub(self.context).max(next);
+            let to = (hit + self.context).min(count - 1);
+            for n in from..=to {
+                lines.push(Line {
+                    number: n + 1,
+                    text: text(n),
+                    is_match: hits.binary_search(&n).is_ok(),
+                });
+            }
+            next = next.max(to + 1);
+        }
+        lines
+    }
+
+    /// Search files in parallel, results are in the same order as `paths`
+    pub fn search_files(&self, paths: &[PathBuf]) -> Vec<Option<Vec<Line>>> {
+        par_map(paths, self.reader.jobs, |path| self.search(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_search() {
+        let filter =
+            ContentFilter::new("^err|fatal", true, Reader::new(u64::MAX, true, 1), true, 1)
+                .unwrap();
+        let data = b"ok\nERROR one\nok\nok\nok\nfatal\tend\n";
+        let lines = filter.matching_lines(data);
+        let numbers: Vec<(usize, bool)> = lines.iter().map(|l| (l.number, l.is_match)).collect();
+        assert_eq!(
+            numbers,
+            vec![(1, false), (2, true), (3, false), (5, false), (6, true)]
+        );
+        assert_eq!(lines[4].text, "fatal end");
+
+        let dir = TempDir::new("content");
+        let path = dir.join("file");
+        std::fs::write(&path, b"text\0binary\nfatal\n").unwrap();
+        assert!(filter.search(&path).is_none());
+        std::fs::write(&path, b"fatal\n").unwrap();
+        assert!(filter.search(&path).is_some());
+        let small = ContentFilter::new("fatal", false, Reader::new(3, true, 1), false, 0).unwrap();
+        assert!(small.search(&path).is_none());
+        assert!(ContentFilter::new("(", false, Reader::new(1, true, 1), false, 0).is_err());
+
+        // a gzip file is searched and counted by its decoded lines, or skipped as binary
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+        std::io::Write::write_all(&mut gz, b"ok\nfatal\nlast").unwrap();
+        std::fs::write(&path, gz.finish().unwrap()).unwrap();
+        assert_eq!(filter.search(&path).unwrap()[1].text, "fatal");
+        assert_eq!(Reader::new(u64::MAX, true, 1).line_count(&path), "3");
+        assert_eq!(Reader::new(u64::MAX, false, 1).line_count(&path), "-");
+        assert_eq!(Reader::new(4, true, 1).line_count(&path), "-");
+        std::fs::remove_file(&path).unwrap();
+
+        // a fifo without a writer is skipped instead of blocking
+        let fifo = std::ffi::CString::new(path.as_os_str().as_encoded_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        assert!(filter.search(&path).is_none());
+        assert_eq!(Reader::new(u64::MAX, true, 1).line_count(&path), "-");
+        std::fs::remove_file(&path).unwrap();
+
+        // a dangling link is skipped as well, without an error
+        std::os::unix::fs::symlink("missing", &path).unwrap();
+        assert!(regular_file(&path).unwrap().is_none());
+        std::fs::remove_file(&path).unwrap();
+        assert!(regular_file(&path).is_err());
+    }
+}
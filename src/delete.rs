@@ -0,0 +1,321 @@
+use log::{error, info, warn};
+use std::{
+    env,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Error, ErrorKind, IsTerminal, Write},
+    os::unix::fs::{DirBuilderExt, MetadataExt},
+    path::{self, Path, PathBuf},
+};
+
+use crate::process::size_trans;
+
+pub struct DeleteOptions<'a> {
+    pub dry_run: bool,
+    pub yes: bool,
+    pub remove_dirs: bool,
+    pub trash: bool,
+    pub audit_log: Option<&'a String>,
+    pub size_fmt: &'a str,
+}
+
+/// Remove matched entries, or move them to the trash. Directories are only removed when empty,
+/// so the entries are expected in depth-first order. Entries that fail are reported and skipped,
+/// returns true if every entry was handled
+pub fn delete_entries(paths: &[PathBuf], opts: &DeleteOptions) -> Result<bool, Error> {
+    let mut success = true;
+    let (mut files, mut dirs, mut bytes) = (0usize, 0usize, 0u64);
+    for path in paths {
+        let meta = match fs::symlink_metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                warn!("failed to read `{}`: {}", path.display(), e);
+                success = false;
+                continue;
+            }
+        };
+        if meta.is_dir() {
+            if opts.remove_dirs {
+                dirs += 1;
+            }
+        } else {
+            files += 1;
+            bytes += meta.len();
+        }
+    }
+    eprintln!(
+        "{} {} file(s) and {} empty dir(s), {} would be freed",
+        if opts.trash { "trash" } else { "delete" },
+        files,
+        dirs,
+        size_trans(bytes as f64, opts.size_fmt)
+    );
+
+    if opts.dry_run || files + dirs == 0 {
+        return Ok(success);
+    }
+    if !opts.yes {
+        match confirm()? {
+            Some(true) => {}
+            Some(false) => {
+                eprintln!("aborted, nothing was deleted");
+                return Ok(success);
+            }
+            None => {
+                eprintln!(
+                    "error: refuse to delete without confirmation, use `--yes` in non-interactive mode"
+                );
+                return Ok(false);
+            }
+        }
+    }
+
+    let mut audit = match opts.audit_log {
+        Some(log) => Some(OpenOptions::new().create(true).append(true).open(log)?),
+        None => None,
+    };
+    let mut trash = None;
+    let (mut removed_files, mut removed_dirs, mut freed) = (0usize, 0usize, 0u64);
+
+    for path in paths {
+        let meta = match fs::symlink_metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                error!("failed to delete `{}`: {}", path.display(), e);
+                success = false;
+                continue;
+            }
+        };
+        if meta.is_dir() && !opts.remove_dirs {
+            continue;
+        }
+        let res = if meta.is_dir() {
+            fs::read_dir(path).and_then(|mut children| match children.next() {
+                Some(_) => Err(Error::from(ErrorKind::DirectoryNotEmpty)),
+                None => Ok(()),
+            })
+        } else {
+            Ok(())
+        }
+        .and_then(|_| {
+            if opts.trash {
+                let trash = trash.get_or_insert_with(Trash::new);
+                trash.put(path, &meta).map(|_| "trash")
+            } else if meta.is_dir() {
+                fs::remove_dir(path).map(|_| "rmdir")
+            } else {
+                fs::remove_file(path).map(|_| "delete")
+            }
+        });
+
+        match res {
+            Ok(action) => {
+                if meta.is_dir() {
+                    removed_dirs += 1;
+                } else {
+                    removed_files += 1;
+                    freed += meta.len();
+                }
+                if let Some(audit) = audit.as_mut() {
+                    let res = writeln!(
+                        audit,
+                        "{}\t{}\t{}\t{}",
+                        jiff::Zoned::now().strftime("%Y-%m-%dT%H:%M:%S%:z"),
+                        action,
+                        meta.len(),
+                        path.display()
+                    );
+                    if let Err(e) = res {
+                        error!("failed to log `{}` to the audit log: {}", path.display(), e);
+                        success = false;
+                    }
+                }
+            }
+            // non-empty directories are expected when only some of their children match
+            Err(e) if e.kind() == ErrorKind::DirectoryNotEmpty => {
+                warn!("skip non-empty dir `{}`", path.display());
+            }
+            Err(e) => {
+                error!("failed to delete `{}`: {}", path.display(), e);
+                success = false;
+            }
+        }
+    }
+    info!(
+        "{} {} file(s) and {} dir(s), freed: {}",
+        if opts.trash { "trashed" } else { "deleted" },
+        removed_files,
+        removed_dirs,
+        size_trans(freed as f64, opts.size_fmt)
+    );
+
+    Ok(success)
+}
+
+/// Ask for confirmation on the terminal, returns None if stdin is not a terminal
+pub fn confirm() -> Result<Option<bool>, Error> {
+    if !io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    eprint!("continue? [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(Some(matches!(answer.trim(), "y" | "Y" | "yes" | "YES")))
+}
+
+// freedesktop.org trash, see https://specifications.freedesktop.org/trash-spec/latest/
+struct Trash {
+    home: Option<PathBuf>,
+}
+
+impl Trash {
+    fn new() -> Self {
+        let home = env::var_os("XDG_DATA_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".local/share")))
+            .map(|data| data.join("Trash"));
+        Trash { home }
+    }
+
+    // trash dir on the same device as the item, renaming across devices is not possible
+    fn trash_dir(&self, path: &Path, meta: &fs::Metadata) -> Result<PathBuf, Error> {
+        if let Some(home) = self.home.as_ref() {
+            make_trash_dir(home)?;
+            if fs::metadata(home)?.dev() == meta.dev() {
+                return Ok(home.clone());
+            }
+        }
+
+        let abs = path::absolute(path)?;
+        let mut topdir = abs.parent().unwrap_or(Path::new("/")).to_path_buf();
+        while let Some(parent) = topdir.parent() {
+            if fs::metadata(parent)?.dev() != meta.dev() {
+                break;
+            }
+            topdir = parent.to_path_buf();
+        }
+        let dir = topdir.join(format!(".Trash-{}", unsafe { libc::getuid() }));
+        make_trash_dir(&dir)?;
+        Ok(dir)
+    }
+
+    fn put(&mut self, path: &Path, meta: &fs::Metadata) -> Result<(), Error> {
+        let dir = self.trash_dir(path, meta)?;
+        let abs = path::absolute(path)?;
+        let name = abs
+            .file_name()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path has no file name"))?;
+
+        // reserve an unused name by creating its info file first
+        let mut idx = 1;
+        let mut trash_name: OsString = name.to_os_string();
+        let mut info = loop {
+            let mut info_name = trash_name.clone();
+            info_name.push(".trashinfo");
+            match File::create_new(dir.join("info").join(&info_name)) {
+                Ok(fp) if !dir.join("files").join(&trash_name).exists() => break fp,
+                Ok(_) => {
+                    fs::remove_file(dir.join("info").join(&info_name))?;
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+            idx += 1;
+            trash_name = name.to_os_string();
+            trash_name.push(format!(".{}", idx));
+        };
+
+        write!(
+            info,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            url_encode(&abs),
+            jiff::Zoned::now().strftime("%Y-%m-%dT%H:%M:%S")
+        )?;
+        if let Err(e) = fs::rename(path, dir.join("files").join(&trash_name)) {
+            let mut info_name = trash_name;
+            info_name.push(".trashinfo");
+            let _ = fs::remove_file(dir.join("info").join(info_name));
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+fn make_trash_dir(dir: &Path) -> Result<(), Error> {
+    for sub in ["files", "info"] {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir.join(sub))?;
+    }
+    Ok(())
+}
+
+fn url_encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut out = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_delete_entries() {
+        let dir = TempDir::new("delete");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a"), "abc").unwrap();
+        fs::write(dir.join("b"), "de").unwrap();
+        let audit_log = dir.join("audit.log").to_string_lossy().into_owned();
+        let opts = DeleteOptions {
+            dry_run: false,
+            yes: true,
+            remove_dirs: true,
+            trash: false,
+            audit_log: Some(&audit_log),
+            size_fmt: "b",
+        };
+
+        // a vanished entry fails on its own, the others are still removed and logged
+        let paths = [
+            dir.join("sub/a"),
+            dir.join("gone"),
+            dir.join("sub"),
+            dir.join("b"),
+        ];
+        let ok = delete_entries(&paths, &opts).unwrap();
+        let audit = fs::read_to_string(&audit_log).unwrap();
+        let remaining: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name())
+            .collect();
+        assert!(!ok);
+        assert_eq!(remaining, ["audit.log"]);
+        let actions: Vec<_> = audit
+            .lines()
+            .map(|l| l.split('\t').nth(1).unwrap())
+            .collect();
+        assert_eq!(actions, ["delete", "rmdir", "delete"]);
+    }
+
+    #[test]
+    fn test_url_encode() {
+        assert_eq!(
+            url_encode(Path::new("/data/a b%.txt")),
+            "/data/a%20b%25.txt"
+        );
+    }
+}
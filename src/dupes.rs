@@ -0,0 +1,450 @@
+use log::{debug, error, info, warn};
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, FileTimes},
+    io::{self, BufReader, BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    os::{fd::AsRawFd, unix::fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+use tabwriter::TabWriter;
+
+use crate::{
+    cli::{DupesOpt, OutFormat},
+    delete::confirm,
+    filetype::FileKind,
+    process::{collect_entries, size_trans},
+    report::csv_row,
+    temp::{create_temp, create_temp_with},
+};
+
+// bytes read from the head and the tail of a file for the partial hash
+const PARTIAL_SIZE: u64 = 4096;
+
+/// A group of files with identical content
+pub struct DupeSet {
+    pub size: u64,
+    pub hash: String,
+    pub paths: Vec<PathBuf>,
+}
+
+impl DupeSet {
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Group files by size, then by a hash of their first and last blocks, then by a full content hash.
+/// Hard links to the same inode are counted as one file, empty files are ignored.
+/// Files that can't be read are skipped with a warning, the flag is false if there were any
+pub fn find_dupe_sets(paths: &[PathBuf]) -> (Vec<DupeSet>, bool) {
+    let mut ok = true;
+    let mut inodes = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for path in paths {
+        let meta = match fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                warn!("failed to read `{}`: {}", path.display(), e);
+                ok = false;
+                continue;
+            }
+        };
+        if meta.len() == 0 {
+            continue;
+        }
+        if !inodes.insert((meta.dev(), meta.ino())) {
+            debug!("skip hard link `{}`", path.display());
+            continue;
+        }
+        by_size.entry(meta.len()).or_default().push(path.clone());
+    }
+
+    let mut sets = vec![];
+    for (size, group) in by_size.into_iter().filter(|(_, g)| g.len() > 1) {
+        for (hash, group) in split_by(group, |p| partial_hash(p, size), &mut ok) {
+            // the partial hash already covers small files completely
+            let full = if size <= PARTIAL_SIZE * 2 {
+                vec![(hash, group)]
+            } else {
+                split_by(group, full_hash, &mut ok)
+            };
+            for (hash, paths) in full {
+                sets.push(DupeSet { size, hash, paths });
+            }
+        }
+    }
+
+    for set in sets.iter_mut() {
+        set.paths.sort();
+    }
+    sets.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then(a.paths.cmp(&b.paths)));
+    (sets, ok)
+}
+
+// split a group by a key, only groups with more than one member are kept
+fn split_by<F>(group: Vec<PathBuf>, key: F, ok: &mut bool) -> Vec<(String, Vec<PathBuf>)>
+where
+    F: Fn(&Path) -> Result<String, Error>,
+{
+    let mut map: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for path in group {
+        match key(&path) {
+            Ok(k) => map.entry(k).or_default().push(path),
+            Err(e) => {
+                warn!("failed to read `{}`: {}", path.display(), e);
+                *ok = false;
+            }
+        }
+    }
+    map.into_iter().filter(|(_, g)| g.len() > 1).collect()
+}
+
+fn partial_hash(path: &Path, size: u64) -> Result<String, Error> {
+    let mut fp = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![];
+
+    (&mut fp).take(PARTIAL_SIZE).read_to_end(&mut buf)?;
+    hasher.update(&buf);
+    if size > PARTIAL_SIZE * 2 {
+        fp.seek(SeekFrom::End(-(PARTIAL_SIZE as i64)))?;
+        fp.read_exact(&mut buf)?;
+        hasher.update(&buf);
+    } else {
+        io::copy(&mut fp, &mut hasher)?;
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn full_hash(path: &Path) -> Result<String, Error> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+pub fn find_dupes(opt: &DupesOpt) -> Result<bool, Error> {
+    let dir = opt.rootdir.as_deref().unwrap_or(".");
+    let files = collect_entries(dir, &opt.filter, None, &[FileKind::File])?;
+    let (sets, ok) = find_dupe_sets(&files);
+
+    let mut fp: Box<dyn Write> = if let Some(out) = opt.out.as_ref() {
+        Box::new(BufWriter::new(File::create(out)?))
+    } else {
+        Box::new(TabWriter::new(BufWriter::new(io::stdout())))
+    };
+
+    match opt.format {
+        OutFormat::Table => {
+            if !opt.header {
+                writeln!(fp, "Set\tSize\tHash\tPath")?;
+            }
+            for (idx, set) in sets.iter().enumerate() {
+                for path in set.paths.iter() {
+                    writeln!(
+                        fp,
+                        "{}\t{}\t{}\t{}",
+                        idx + 1,
+                        size_trans(set.size as f64, &opt.size_fmt),
+                        &set.hash[..16],
+                        path.display()
+                    )?;
+                }
+            }
+        }
+        OutFormat::Csv => {
+            if !opt.header {
+                writeln!(fp, "set,size,hash,path")?;
+            }
+            for (idx, set) in sets.iter().enumerate() {
+                for path in set.paths.iter() {
+                    let row = [
+                        (idx + 1).to_string(),
+                        set.size.to_string(),
+                        set.hash.clone(),
+                        path.to_string_lossy().into_owned(),
+                    ];
+                    writeln!(fp, "{}", csv_row(&row))?;
+                }
+            }
+        }
+        OutFormat::Json => {
+            let json: Vec<_> = sets
+                .iter()
+                .map(|set| {
+                    json!({
+                        "size": set.size,
+                        "hash": set.hash,
+                        "wasted": set.wasted(),
+                        "paths": set.paths.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut fp, &json)?;
+            writeln!(fp)?;
+        }
+    }
+    fp.flush()?;
+
+    let wasted: u64 = sets.iter().map(|s| s.wasted()).sum();
+    info!(
+        "{} duplicate set(s), {} wasted",
+        sets.len(),
+        size_trans(wasted as f64, &opt.size_fmt)
+    );
+
+    if opt.hardlink || opt.reflink {
+        let link = if opt.hardlink {
+            Link::Hard
+        } else {
+            Link::Reflink
+        };
+        return Ok(dedupe_sets(&sets, link, opt.dry_run, opt.yes, &opt.size_fmt)? && ok);
+    }
+    Ok(ok)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Link {
+    Hard,
+    Reflink,
+}
+
+// the oldest file of a set is kept, the others on the same filesystem are replaced by links to it,
+// files that can't be read any more are left out of the plan
+fn dedupe_plan(set: &DupeSet, ok: &mut bool) -> Option<(PathBuf, Vec<PathBuf>)> {
+    let mut metas = vec![];
+    for path in set.paths.iter() {
+        match fs::metadata(path).and_then(|m| Ok((m.modified()?, m.dev()))) {
+            Ok((mtime, dev)) => metas.push((mtime, dev, path.clone())),
+            Err(e) => {
+                warn!("failed to read `{}`: {}", path.display(), e);
+                *ok = false;
+            }
+        }
+    }
+    if metas.is_empty() {
+        return None;
+    }
+    metas.sort();
+    let (_, dev, keep) = metas.remove(0);
+    let replace = metas
+        .into_iter()
+        .filter(|(_, d, p)| {
+            if *d != dev {
+                debug!("skip `{}` on another filesystem", p.display());
+            }
+            *d == dev
+        })
+        .map(|(_, _, p)| p)
+        .collect();
+    Some((keep, replace))
+}
+
+fn dedupe_sets(
+    sets: &[DupeSet],
+    link: Link,
+    dry_run: bool,
+    yes: bool,
+    size_fmt: &str,
+) -> Result<bool, Error> {
+    let (mut plans, mut success) = (vec![], true);
+    let mut saved = 0u64;
+    for set in sets {
+        let Some((keep, replace)) = dedupe_plan(set, &mut success) else {
+            continue;
+        };
+        saved += set.size * replace.len() as u64;
+        if !replace.is_empty() {
+            plans.push((set.size, keep, replace));
+        }
+    }
+    eprintln!(
+        "replace {} file(s) with {}, {} would be saved",
+        plans.iter().map(|p| p.2.len()).sum::<usize>(),
+        if link == Link::Hard {
+            "hard links"
+        } else {
+            "reflinks"
+        },
+        size_trans(saved as f64, size_fmt)
+    );
+
+    if dry_run || plans.is_empty() {
+        return Ok(success);
+    }
+    if !yes {
+        match confirm()? {
+            Some(true) => {}
+            Some(false) => {
+                eprintln!("aborted, nothing was changed");
+                return Ok(success);
+            }
+            None => {
+                eprintln!(
+                    "error: refuse to replace files without confirmation, use `--yes` in non-interactive mode"
+                );
+                return Ok(false);
+            }
+        }
+    }
+
+    let mut saved = 0u64;
+    for (size, keep, replace) in plans.iter() {
+        for dup in replace {
+            // the files might have changed since they were hashed
+            let res = match same_content(keep, dup) {
+                Ok(true) => replace_with_link(keep, dup, link),
+                Ok(false) => Err(Error::other("content differs from the kept file")),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(()) => saved += size,
+                Err(e) => {
+                    error!(
+                        "failed to link `{}` to `{}`: {}",
+                        dup.display(),
+                        keep.display(),
+                        e
+                    );
+                    success = false;
+                }
+            }
+        }
+    }
+    info!("saved: {}", size_trans(saved as f64, size_fmt));
+
+    Ok(success)
+}
+
+pub fn same_content(a: &Path, b: &Path) -> Result<bool, Error> {
+    let (mut fa, mut fb) = (
+        BufReader::new(File::open(a)?),
+        BufReader::new(File::open(b)?),
+    );
+    let (mut buf_a, mut buf_b) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    loop {
+        let n = fa.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(fb.read(&mut buf_b[..1])? == 0);
+        }
+        if fb.read_exact(&mut buf_b[..n]).is_err() || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+// write the link to a temporary name next to the duplicate, then rename it over the duplicate
+fn replace_with_link(keep: &Path, dup: &Path, link: Link) -> Result<(), Error> {
+    let (tmp, res) = match link {
+        Link::Hard => (
+            create_temp_with(dup, |tmp| fs::hard_link(keep, tmp))?.0,
+            Ok(()),
+        ),
+        Link::Reflink => {
+            let (tmp, fp) = create_temp(dup)?;
+            let res = reflink(keep, dup, fp);
+            (tmp, res)
+        }
+    };
+    if let Err(e) = res.and_then(|_| fs::rename(&tmp, dup)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+// clone the data of `src` with FICLONE into the empty temporary file, which keeps the
+// permissions and times of `dup`
+fn reflink(src: &Path, dup: &Path, tmp_fp: File) -> Result<(), Error> {
+    let meta = fs::metadata(dup)?;
+    let src_fp = File::open(src)?;
+
+    if unsafe { libc::ioctl(tmp_fp.as_raw_fd(), libc::FICLONE, src_fp.as_raw_fd()) } != 0 {
+        let e = Error::last_os_error();
+        return Err(match e.raw_os_error() {
+            Some(libc::EOPNOTSUPP) | Some(libc::EXDEV) | Some(libc::EINVAL) => Error::new(
+                ErrorKind::Unsupported,
+                format!("reflink is not supported by the filesystem: {}", e),
+            ),
+            _ => e,
+        });
+    }
+    tmp_fp.set_permissions(meta.permissions())?;
+    tmp_fp.set_times(
+        FileTimes::new()
+            .set_accessed(meta.accessed()?)
+            .set_modified(meta.modified()?),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_wasted() {
+        let set = DupeSet {
+            size: 100,
+            hash: String::new(),
+            paths: vec![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")],
+        };
+        assert_eq!(set.wasted(), 200);
+    }
+
+    #[test]
+    fn test_find_dupe_sets() {
+        let dir = TempDir::new("dupes-sets");
+        let paths: Vec<PathBuf> = ["a", "b", "c", "gone"]
+            .iter()
+            .map(|n| dir.join(n))
+            .collect();
+        fs::write(&paths[0], "same").unwrap();
+        fs::write(&paths[1], "same").unwrap();
+        fs::write(&paths[2], "other").unwrap();
+
+        // a file that vanished is skipped and reported, the others are still grouped
+        let (sets, ok) = find_dupe_sets(&paths);
+        assert!(!ok);
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].paths, paths[..2]);
+
+        let mut ok = true;
+        let (keep, replace) = dedupe_plan(&sets[0], &mut ok).unwrap();
+        assert!(ok);
+        assert_eq!((keep, replace.len()), (paths[0].clone(), 1));
+        fs::remove_file(&paths[1]).unwrap();
+        let (keep, replace) = dedupe_plan(&sets[0], &mut ok).unwrap();
+        assert!(!ok);
+        assert_eq!((keep, replace.len()), (paths[0].clone(), 0));
+    }
+
+    #[test]
+    fn test_replace_with_link() {
+        let dir = TempDir::new("dupes");
+        let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        fs::write(&c, "sam").unwrap();
+        assert!(same_content(&a, &b).unwrap());
+        assert!(!same_content(&a, &c).unwrap());
+        assert!(!same_content(&c, &a).unwrap());
+
+        // a file with the first temporary name is left alone
+        let taken = dir.join(format!(".b.fdx-tmp-{}-0", std::process::id()));
+        fs::write(&taken, "mine").unwrap();
+        replace_with_link(&a, &b, Link::Hard).unwrap();
+        let ino = |p: &Path| fs::metadata(p).unwrap().ino();
+        assert_eq!(ino(&a), ino(&b));
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "mine");
+
+        // a failed reflink removes only its own temporary file and keeps the duplicate
+        if replace_with_link(&a, &c, Link::Reflink).is_err() {
+            assert_eq!(fs::read_to_string(&c).unwrap(), "sam");
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+        }
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "mine");
+    }
+}
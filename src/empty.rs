@@ -0,0 +1,89 @@
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+};
+
+/// `--empty` and `--empty-dirs-recursive`
+pub struct EmptyFilter {
+    empty: bool,
+    recursive: bool,
+    // directories already known to hold nothing but empty directories
+    cache: HashMap<PathBuf, bool>,
+}
+
+impl EmptyFilter {
+    pub fn new(empty: bool, recursive: bool) -> Self {
+        EmptyFilter {
+            empty,
+            recursive,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.empty || self.recursive
+    }
+
+    /// Zero-byte regular files and directories without entries with `--empty`, directories
+    /// containing only empty directories with `--empty-dirs-recursive`. Hidden and ignored
+    /// entries count, so a directory is never reported empty while it still holds something
+    pub fn is_match(&mut self, path: &Path, meta: &Metadata) -> bool {
+        if meta.is_file() {
+            return self.empty && meta.len() == 0;
+        }
+        if !meta.is_dir() {
+            return false;
+        }
+        if self.recursive {
+            return self.only_empty_dirs(path);
+        }
+        self.empty && fs::read_dir(path).is_ok_and(|mut rd| rd.next().is_none())
+    }
+
+    // symlinks are not followed, a link inside a directory makes it non-empty
+    fn only_empty_dirs(&mut self, path: &Path) -> bool {
+        if let Some(res) = self.cache.get(path) {
+            return *res;
+        }
+        let res = match fs::read_dir(path) {
+            Ok(rd) => rd.into_iter().all(|e| {
+                e.is_ok_and(|e| {
+                    e.file_type().is_ok_and(|t| t.is_dir()) && self.only_empty_dirs(&e.path())
+                })
+            }),
+            Err(_) => false,
+        };
+        self.cache.insert(path.to_path_buf(), res);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_empty() {
+        let root = TempDir::new("empty");
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::create_dir_all(root.join("d/e")).unwrap();
+        fs::write(root.join("d/e/zero"), "").unwrap();
+        fs::write(root.join("d/data"), "x").unwrap();
+
+        let meta = |p: &str| fs::metadata(root.join(p)).unwrap();
+        let mut empty = EmptyFilter::new(true, false);
+        assert!(empty.is_match(&root.join("a/b/c"), &meta("a/b/c")));
+        assert!(!empty.is_match(&root.join("a/b"), &meta("a/b")));
+        assert!(empty.is_match(&root.join("d/e/zero"), &meta("d/e/zero")));
+        assert!(!empty.is_match(&root.join("d/data"), &meta("d/data")));
+
+        let mut recursive = EmptyFilter::new(false, true);
+        assert!(recursive.is_match(&root.join("a"), &meta("a")));
+        assert!(recursive.is_match(&root.join("a/b/c"), &meta("a/b/c")));
+        assert!(!recursive.is_match(&root.join("d/e"), &meta("d/e")));
+        assert!(!recursive.is_match(&root.join("d/e/zero"), &meta("d/e/zero")));
+        assert!(!recursive.is_match(&root, &meta("")));
+    }
+}
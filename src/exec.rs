@@ -0,0 +1,288 @@
+use log::{error, info};
+use std::{
+    ffi::OsString,
+    io::{self, Error, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::Mutex,
+};
+
+use crate::{content::par_map, pattern::RegexFilter};
+
+// conservative command line budget, the real limit on linux is usually much larger
+const ARG_MAX: usize = 128 * 1024;
+const PLACEHOLDERS: [&str; 5] = ["{}", "{name}", "{stem}", "{parent}", "{ext}"];
+
+pub struct CommandTemplate {
+    args: Vec<String>,
+    // named capture groups of `-r`, available as `{group}` placeholders
+    captures: Option<(RegexFilter, PathBuf)>,
+}
+
+impl CommandTemplate {
+    pub fn new(args: &[String], captures: Option<(RegexFilter, PathBuf)>) -> Self {
+        let mut tpl = CommandTemplate {
+            args: args.to_vec(),
+            captures,
+        };
+        // if no placeholder is given, append the path to the end of the command
+        if !tpl.args.iter().any(|arg| tpl.has_placeholder(arg)) {
+            tpl.args.push("{}".to_string());
+        }
+        tpl
+    }
+
+    fn capture_names(&self) -> &[String] {
+        self.captures
+            .as_ref()
+            .map(|(regex, _)| regex.capture_names())
+            .unwrap_or_default()
+    }
+
+    fn expand_arg(&self, arg: &str, path: &Path) -> OsString {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        let stem = path.file_stem().unwrap_or(name);
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.as_os_str(),
+            _ => ".".as_ref(),
+        };
+        let ext = path.extension().unwrap_or_default();
+
+        // substitute in a single pass, so braces inside file names are kept as is
+        let mut out = OsString::new();
+        let mut rest = arg;
+        while let Some(pos) = rest.find('{') {
+            out.push(&rest[..pos]);
+            rest = &rest[pos..];
+            let value = if rest.starts_with("{}") {
+                Some(("{}", path.as_os_str()))
+            } else if rest.starts_with("{name}") {
+                Some(("{name}", name))
+            } else if rest.starts_with("{stem}") {
+                Some(("{stem}", stem))
+            } else if rest.starts_with("{parent}") {
+                Some(("{parent}", parent))
+            } else if rest.starts_with("{ext}") {
+                Some(("{ext}", ext))
+            } else {
+                None
+            };
+            match value {
+                Some((token, value)) => {
+                    out.push(value);
+                    rest = &rest[token.len()..];
+                }
+                None => match self.capture(rest, path) {
+                    Some((len, value)) => {
+                        out.push(value);
+                        rest = &rest[len..];
+                    }
+                    None => {
+                        out.push("{");
+                        rest = &rest[1..];
+                    }
+                },
+            }
+        }
+        out.push(rest);
+        out
+    }
+
+    // `{group}` at the start of `rest` -> length of the placeholder and the captured value
+    fn capture(&self, rest: &str, path: &Path) -> Option<(usize, String)> {
+        let (regex, root) = self.captures.as_ref()?;
+        let idx = regex.capture_names().iter().position(|name| {
+            rest.strip_prefix('{')
+                .and_then(|r| r.strip_prefix(name.as_str()))
+                .is_some_and(|r| r.starts_with('}'))
+        })?;
+        let len = regex.capture_names()[idx].len() + 2;
+        Some((len, regex.captures(path, root).swap_remove(idx)))
+    }
+
+    fn has_placeholder(&self, arg: &str) -> bool {
+        PLACEHOLDERS.iter().any(|p| arg.contains(p))
+            || self
+                .capture_names()
+                .iter()
+                .any(|name| arg.contains(&format!("{{{}}}", name)))
+    }
+
+    // one command line for a single path
+    fn build(&self, path: &Path) -> Vec<OsString> {
+        self.args
+            .iter()
+            .map(|arg| self.expand_arg(arg, path))
+            .collect()
+    }
+
+    // one command line for a batch of paths, each argument with a placeholder is repeated per path
+    fn build_batch(&self, paths: &[PathBuf]) -> Vec<OsString> {
+        let mut cmd = vec![];
+        for arg in self.args.iter() {
+            if self.has_placeholder(arg) {
+                cmd.extend(paths.iter().map(|p| self.expand_arg(arg, p)));
+            } else {
+                cmd.push(arg.into());
+            }
+        }
+        cmd
+    }
+
+    // split paths into batches that fit in the command line limit
+    fn batches<'a>(&self, paths: &'a [PathBuf]) -> Vec<&'a [PathBuf]> {
+        let env_size: usize = std::env::vars_os()
+            .map(|(k, v)| k.len() + v.len() + 2 + size_of::<usize>())
+            .sum();
+        let fixed: usize = self
+            .args
+            .iter()
+            .filter(|arg| !self.has_placeholder(arg))
+            .map(|arg| arg.len() + 1 + size_of::<usize>())
+            .sum();
+        let limit = ARG_MAX.saturating_sub(env_size + fixed).max(1);
+
+        let mut batches = vec![];
+        let (mut start, mut used) = (0usize, 0usize);
+        for (idx, path) in paths.iter().enumerate() {
+            let cost: usize = self
+                .args
+                .iter()
+                .filter(|arg| self.has_placeholder(arg))
+                .map(|arg| self.expand_arg(arg, path).len() + 1 + size_of::<usize>())
+                .sum();
+            if idx > start && used + cost > limit {
+                batches.push(&paths[start..idx]);
+                start = idx;
+                used = 0;
+            }
+            used += cost;
+        }
+        if start < paths.len() {
+            batches.push(&paths[start..]);
+        }
+        batches
+    }
+}
+
+/// Run the command once for each path, returns true if every command exits successfully
+pub fn exec_each(
+    template: &CommandTemplate,
+    paths: &[PathBuf],
+    jobs: usize,
+) -> Result<bool, Error> {
+    let cmds: Vec<Vec<OsString>> = paths.iter().map(|p| template.build(p)).collect();
+    run_parallel(&cmds, jobs)
+}
+
+/// Run the command with as many paths per invocation as the command line limit allows
+pub fn exec_batch(
+    template: &CommandTemplate,
+    paths: &[PathBuf],
+    jobs: usize,
+) -> Result<bool, Error> {
+    if paths.is_empty() {
+        return Ok(true);
+    }
+    let cmds: Vec<Vec<OsString>> = template
+        .batches(paths)
+        .into_iter()
+        .map(|batch| template.build_batch(batch))
+        .collect();
+    info!("run {} command(s) for {} item(s)", cmds.len(), paths.len());
+    run_parallel(&cmds, jobs)
+}
+
+fn run_parallel(cmds: &[Vec<OsString>], jobs: usize) -> Result<bool, Error> {
+    let output_lock = Mutex::new(());
+    let results = par_map(cmds, jobs, |cmd| match run_command(cmd) {
+        Ok(output) => {
+            // hold the lock so that outputs of different commands never interleave
+            let _guard = output_lock.lock().unwrap();
+            let _ = io::stdout().write_all(&output.stdout);
+            let _ = io::stderr().write_all(&output.stderr);
+            output.status.success()
+        }
+        Err(e) => {
+            error!("failed to run `{}`: {}", cmd[0].to_string_lossy(), e);
+            false
+        }
+    });
+    io::stdout().flush()?;
+
+    Ok(results.into_iter().all(|ok| ok))
+}
+
+fn run_command(cmd: &[OsString]) -> Result<Output, Error> {
+    Command::new(&cmd[0]).args(&cmd[1..]).output()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::MatchOn;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let tpl = CommandTemplate::new(
+            &[
+                "echo".to_string(),
+                "{parent}/{stem}.{ext}".to_string(),
+                "{name}".to_string(),
+            ],
+            None,
+        );
+        assert_eq!(
+            tpl.build(Path::new("data/sample.bam")),
+            vec!["echo", "data/sample.bam", "sample.bam"]
+        );
+        assert_eq!(tpl.build(Path::new("a.txt"))[1], "./a.txt");
+
+        let regex = RegexFilter::new(
+            &["(?P<sample>[^_]+)_(?P<read>R[12])".to_string()],
+            &[],
+            false,
+            MatchOn::Name,
+            false,
+        )
+        .unwrap();
+        let tpl = CommandTemplate::new(
+            &["echo".to_string(), "{sample}.{read}".to_string()],
+            Some((regex, PathBuf::from("."))),
+        );
+        assert_eq!(tpl.build(Path::new("./NA1_R2.fq")), vec!["echo", "NA1.R2"]);
+    }
+
+    #[test]
+    fn test_capture_placeholders() {
+        let regex = RegexFilter::new(
+            &["(?P<sample>[^_]+)_(?P<read>R[12])?".to_string()],
+            &[],
+            false,
+            MatchOn::Relpath,
+            false,
+        )
+        .unwrap();
+        let tpl = CommandTemplate::new(
+            &["cat".to_string(), "{sample}/{read}".to_string()],
+            Some((regex, PathBuf::from("fq"))),
+        );
+        // a group placeholder counts as a placeholder, the path is not appended, and a group that
+        // did not match expands to nothing. Unknown names are kept as is
+        let paths = vec![PathBuf::from("fq/NA1_R1.fq"), PathBuf::from("fq/NA2_x.fq")];
+        assert_eq!(tpl.build_batch(&paths), vec!["cat", "NA1/R1", "NA2/"]);
+        assert_eq!(
+            tpl.expand_arg("{lane}-{sample}", &paths[0]),
+            OsString::from("{lane}-NA1")
+        );
+    }
+
+    #[test]
+    fn test_append_path() {
+        let tpl = CommandTemplate::new(&["ls".to_string(), "-l".to_string()], None);
+        assert_eq!(tpl.build(Path::new("x")), vec!["ls", "-l", "x"]);
+        let paths = vec![PathBuf::from("a"), PathBuf::from("b")];
+        assert_eq!(tpl.build_batch(&paths), vec!["ls", "-l", "a", "b"]);
+        assert_eq!(tpl.batches(&paths).len(), 1);
+    }
+}
@@ -0,0 +1,731 @@
+//! The `--where` filter expression language.
+//!
+//! ```text
+//! expr  := or
+//! or    := and (("or" | "||") and)*
+//! and   := not (("and" | "&&") not)*
+//! not   := ("not" | "!") not | "(" expr ")" | cond
+//! cond  := attr op value | attr "in" "[" value ("," value)* "]"
+//! op    := "==" | "=" | "!=" | "<" | "<=" | ">" | ">=" | "~" | "!~"
+//! ```
+//!
+//! Values are bare words or quoted with `'` or `"`. The expression is parsed and checked once,
+//! then evaluated per entry. Both sides of `and`/`or` are free of side effects, so the cheaper
+//! side is evaluated first and hashes are only read when a condition needs them.
+
+use clap::ValueEnum;
+use regex::Regex;
+use std::{
+    fmt,
+    fs::Metadata,
+    io::{Error, ErrorKind},
+    os::unix::fs::MetadataExt,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use walkdir::DirEntry;
+
+use crate::{
+    ext::has_ext,
+    filetype::FileKind,
+    hash::{HashAlgo, hash_file},
+    owner::{resolve_group, resolve_user},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Attr {
+    Name,
+    Path,
+    Ext,
+    Type,
+    Depth,
+    Size,
+    Mode,
+    Owner,
+    Group,
+    Mtime,
+    Atime,
+    Ctime,
+    Hash(HashAlgo),
+}
+
+const ATTRS: [(&str, Attr); 16] = [
+    ("name", Attr::Name),
+    ("path", Attr::Path),
+    ("ext", Attr::Ext),
+    ("type", Attr::Type),
+    ("depth", Attr::Depth),
+    ("size", Attr::Size),
+    ("mode", Attr::Mode),
+    ("owner", Attr::Owner),
+    ("group", Attr::Group),
+    ("mtime", Attr::Mtime),
+    ("atime", Attr::Atime),
+    ("ctime", Attr::Ctime),
+    ("sha256", Attr::Hash(HashAlgo::Sha256)),
+    ("blake3", Attr::Hash(HashAlgo::Blake3)),
+    ("xxh3", Attr::Hash(HashAlgo::Xxh3)),
+    ("md5", Attr::Hash(HashAlgo::Md5)),
+];
+
+impl Attr {
+    fn is_numeric(&self) -> bool {
+        !matches!(
+            self,
+            Attr::Name | Attr::Path | Attr::Ext | Attr::Type | Attr::Hash(_)
+        )
+    }
+
+    // the entry and its metadata are at hand, only hashes read the file
+    fn cost(&self) -> u8 {
+        match self {
+            Attr::Hash(_) => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+    In,
+}
+
+impl Op {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+            Op::In => "in",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Num(i64),
+    Kind(FileKind),
+}
+
+#[derive(Debug)]
+enum Test {
+    In(Vec<Value>),
+    NotIn(Vec<Value>),
+    Cmp(Op, i64),
+    Regex(Regex, bool),
+}
+
+#[derive(Debug)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Cond(Attr, Test),
+}
+
+/// A parsed `--where` expression
+#[derive(Debug)]
+pub struct Expr(Node);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    And,
+    Or,
+    Not,
+    Op(Op),
+    Word(String),
+    Str(String),
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::LParen => write!(f, "`(`"),
+            Tok::RParen => write!(f, "`)`"),
+            Tok::LBracket => write!(f, "`[`"),
+            Tok::RBracket => write!(f, "`]`"),
+            Tok::Comma => write!(f, "`,`"),
+            Tok::And => write!(f, "`and`"),
+            Tok::Or => write!(f, "`or`"),
+            Tok::Not => write!(f, "`not`"),
+            Tok::Op(op) => write!(f, "`{}`", op.symbol()),
+            Tok::Word(w) => write!(f, "`{}`", w),
+            Tok::Str(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+struct ParseError {
+    pos: usize,
+    msg: String,
+}
+
+fn err<T>(pos: usize, msg: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        pos,
+        msg: msg.into(),
+    })
+}
+
+const SPECIAL: &str = "()[],'\"=!<>~&|";
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Tok)>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        chars.next();
+        let next = chars.peek().map(|(_, c)| *c);
+        let mut two = |tok: Tok| {
+            chars.next();
+            tok
+        };
+        let tok = match (c, next) {
+            ('(', _) => Tok::LParen,
+            (')', _) => Tok::RParen,
+            ('[', _) => Tok::LBracket,
+            (']', _) => Tok::RBracket,
+            (',', _) => Tok::Comma,
+            ('=', Some('=')) => two(Tok::Op(Op::Eq)),
+            ('=', _) => Tok::Op(Op::Eq),
+            ('!', Some('=')) => two(Tok::Op(Op::Ne)),
+            ('!', Some('~')) => two(Tok::Op(Op::NotMatch)),
+            ('!', _) => Tok::Not,
+            ('<', Some('=')) => two(Tok::Op(Op::Le)),
+            ('<', _) => Tok::Op(Op::Lt),
+            ('>', Some('=')) => two(Tok::Op(Op::Ge)),
+            ('>', _) => Tok::Op(Op::Gt),
+            ('~', _) => Tok::Op(Op::Match),
+            ('&', Some('&')) => two(Tok::And),
+            ('|', Some('|')) => two(Tok::Or),
+            ('&' | '|', _) => return err(pos, format!("unexpected `{}`, use `{}{}`", c, c, c)),
+            ('\'' | '"', _) => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, e)) => s.push(e),
+                            None => return err(pos, "unterminated string"),
+                        },
+                        Some((_, q)) if q == c => break,
+                        Some((_, ch)) => s.push(ch),
+                        None => return err(pos, "unterminated string"),
+                    }
+                }
+                Tok::Str(s)
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&(_, ch)) = chars.peek() {
+                    if ch.is_whitespace() || SPECIAL.contains(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                match word.to_lowercase().as_str() {
+                    "and" => Tok::And,
+                    "or" => Tok::Or,
+                    "not" => Tok::Not,
+                    "in" => Tok::Op(Op::In),
+                    _ => Tok::Word(word),
+                }
+            }
+        };
+        tokens.push((pos, tok));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Tok)],
+    idx: usize,
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.idx).map(|(_, t)| t)
+    }
+
+    fn pos(&self) -> usize {
+        self.tokens
+            .get(self.idx)
+            .map(|(p, _)| *p)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<(usize, Tok)> {
+        let tok = self.tokens.get(self.idx).cloned();
+        self.idx += 1;
+        tok
+    }
+
+    fn expect(&mut self, want: Tok, what: &str) -> Result<(), ParseError> {
+        match self.next() {
+            Some((_, tok)) if tok == want => Ok(()),
+            Some((pos, tok)) => err(pos, format!("expected {}, found {}", what, tok)),
+            None => err(
+                self.end,
+                format!("expected {}, found end of expression", what),
+            ),
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, ParseError> {
+        let mut left = self.and()?;
+        while self.peek() == Some(&Tok::Or) {
+            self.next();
+            left = cheap_first(Node::Or, left, self.and()?);
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Node, ParseError> {
+        let mut left = self.not()?;
+        while self.peek() == Some(&Tok::And) {
+            self.next();
+            left = cheap_first(Node::And, left, self.not()?);
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Node, ParseError> {
+        match self.peek() {
+            Some(Tok::Not) => {
+                self.next();
+                Ok(Node::Not(Box::new(self.not()?)))
+            }
+            Some(Tok::LParen) => {
+                self.next();
+                let expr = self.or()?;
+                self.expect(Tok::RParen, "`)`")?;
+                Ok(expr)
+            }
+            _ => self.cond(),
+        }
+    }
+
+    fn cond(&mut self) -> Result<Node, ParseError> {
+        let (pos, attr) = match self.next() {
+            Some((pos, Tok::Word(w))) => (pos, w),
+            Some((pos, tok)) => return err(pos, format!("expected an attribute, found {}", tok)),
+            None => return err(self.end, "expected an attribute, found end of expression"),
+        };
+        let Some(&(_, attr)) = ATTRS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&attr))
+        else {
+            let names: Vec<&str> = ATTRS.iter().map(|(name, _)| *name).collect();
+            return err(
+                pos,
+                format!(
+                    "unknown attribute `{}`, expected one of: {}",
+                    attr,
+                    names.join(", ")
+                ),
+            );
+        };
+
+        let (op_pos, op) = match self.next() {
+            Some((pos, Tok::Op(op))) => (pos, op),
+            Some((pos, tok)) => return err(pos, format!("expected an operator, found {}", tok)),
+            None => return err(self.end, "expected an operator, found end of expression"),
+        };
+        let test = match op {
+            Op::In => {
+                self.expect(Tok::LBracket, "`[`")?;
+                let mut values = vec![self.value(attr)?];
+                while self.peek() == Some(&Tok::Comma) {
+                    self.next();
+                    values.push(self.value(attr)?);
+                }
+                self.expect(Tok::RBracket, "`,` or `]`")?;
+                Test::In(values)
+            }
+            Op::Eq => Test::In(vec![self.value(attr)?]),
+            Op::Ne => Test::NotIn(vec![self.value(attr)?]),
+            Op::Match | Op::NotMatch => {
+                if attr.is_numeric() {
+                    return err(
+                        op_pos,
+                        "`~` only applies to name, path, ext, type and hashes",
+                    );
+                }
+                let pos = self.pos();
+                let pattern = self.raw()?;
+                match Regex::new(&pattern) {
+                    Ok(re) => Test::Regex(re, op == Op::NotMatch),
+                    Err(e) => return err(pos, format!("invalid regex: {}", e)),
+                }
+            }
+            _ => {
+                if !attr.is_numeric() {
+                    return err(op_pos, "ordering only applies to numbers, sizes and times");
+                }
+                let Value::Num(n) = self.value(attr)? else {
+                    unreachable!()
+                };
+                Test::Cmp(op, n)
+            }
+        };
+        Ok(Node::Cond(attr, test))
+    }
+
+    fn raw(&mut self) -> Result<String, ParseError> {
+        match self.next() {
+            Some((_, Tok::Word(w) | Tok::Str(w))) => Ok(w),
+            Some((pos, tok)) => err(pos, format!("expected a value, found {}", tok)),
+            None => err(self.end, "expected a value, found end of expression"),
+        }
+    }
+
+    fn value(&mut self, attr: Attr) -> Result<Value, ParseError> {
+        let pos = self.pos();
+        let raw = self.raw()?;
+        let parsed = match attr {
+            Attr::Name | Attr::Path => Some(Value::Str(raw.clone())),
+            Attr::Ext => Some(Value::Str(raw.trim_start_matches('.').to_lowercase())),
+            Attr::Hash(_) => Some(Value::Str(raw.to_lowercase())),
+            // the same names and letters as `-T`
+            Attr::Type => match raw.as_str() {
+                "directory" => Some(FileKind::Dir),
+                "link" => Some(FileKind::Symlink),
+                _ => FileKind::from_str(&raw, false).ok(),
+            }
+            .map(Value::Kind),
+            Attr::Depth => raw.parse().ok().map(Value::Num),
+            Attr::Owner => resolve_user(&raw).ok().map(|id| Value::Num(id as i64)),
+            Attr::Group => resolve_group(&raw).ok().map(|id| Value::Num(id as i64)),
+            Attr::Mode => i64::from_str_radix(&raw, 8).ok().map(Value::Num),
+            Attr::Size => parse_size(&raw).map(Value::Num),
+            Attr::Mtime | Attr::Atime | Attr::Ctime => parse_time(&raw).map(Value::Num),
+        };
+        let expected = match attr {
+            Attr::Type => {
+                "a type: file, dir, symlink, fifo, socket, block, char, executable or broken"
+            }
+            Attr::Depth => "an integer",
+            Attr::Owner => "a user name or uid",
+            Attr::Group => "a group name or gid",
+            Attr::Mode => "an octal mode like 644",
+            Attr::Size => "a size like 512, 10k or 1.5G",
+            Attr::Mtime | Attr::Atime | Attr::Ctime => {
+                "a time before now like -30d, -12h or -2w, or a date like 2024-01-31"
+            }
+            _ => "a value",
+        };
+        parsed.map_or_else(
+            || {
+                err(
+                    pos,
+                    format!("invalid value `{}`, expected {}", raw, expected),
+                )
+            },
+            Ok,
+        )
+    }
+}
+
+// `and`/`or` have no side effects, so the operands can be swapped to evaluate the cheap one first
+fn cheap_first(node: fn(Box<Node>, Box<Node>) -> Node, left: Node, right: Node) -> Node {
+    if right.cost() < left.cost() {
+        node(Box::new(right), Box::new(left))
+    } else {
+        node(Box::new(left), Box::new(right))
+    }
+}
+
+// `1.5G` -> bytes, units are powers of 1024
+fn parse_size(s: &str) -> Option<i64> {
+    let lower = s.to_lowercase();
+    let num = lower.trim_end_matches("ib").trim_end_matches('b');
+    let (num, unit) = match num.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&num[..i], c),
+        _ => (num, 'b'),
+    };
+    let mult = match unit {
+        'b' => 1u64,
+        'k' => 1 << 10,
+        'm' => 1 << 20,
+        'g' => 1 << 30,
+        't' => 1 << 40,
+        _ => return None,
+    };
+    let n: f64 = num.parse().ok()?;
+    (n >= 0.0).then_some((n * mult as f64) as i64)
+}
+
+// an offset before now like `-30d`, in seconds. An unsigned `30d` is rejected, it would read as
+// an age and `<` would mean the opposite of what it means for a date
+fn parse_offset(s: &str) -> Option<i64> {
+    let s = s.strip_prefix('-')?;
+    let i = s.find(|c: char| !c.is_ascii_digit()).filter(|i| *i > 0)?;
+    let unit = match &s[i..] {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        "y" => 365 * 86400,
+        _ => return None,
+    };
+    s[..i].parse::<i64>().ok().map(|n| n * unit)
+}
+
+// an offset before now or a local date/datetime, as unix seconds
+fn parse_time(s: &str) -> Option<i64> {
+    if let Some(offset) = parse_offset(s) {
+        return Some(now() - offset);
+    }
+    let tz = jiff::tz::TimeZone::system();
+    let dt = s
+        .parse::<jiff::civil::DateTime>()
+        .or_else(|_| {
+            s.parse::<jiff::civil::Date>()
+                .map(|d| d.to_datetime(jiff::civil::Time::midnight()))
+        })
+        .ok()?;
+    Some(dt.to_zoned(tz).ok()?.timestamp().as_second())
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn unix_secs(time: std::io::Result<SystemTime>) -> Option<i64> {
+    let time = time.ok()?;
+    Some(match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    })
+}
+
+impl Expr {
+    /// Parse an expression, the error points at the offending token
+    pub fn parse(input: &str) -> Result<Expr, Error> {
+        let parse = || {
+            let tokens = tokenize(input)?;
+            let mut parser = Parser {
+                tokens: &tokens,
+                idx: 0,
+                end: input.len(),
+            };
+            let node = parser.or()?;
+            match parser.next() {
+                None => Ok(Expr(node)),
+                Some((pos, tok)) => err(pos, format!("expected `and` or `or`, found {}", tok)),
+            }
+        };
+        parse().map_err(|e| {
+            let column = input[..e.pos].chars().count();
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "invalid --where expression: {}\n  {}\n  {}^",
+                    e.msg,
+                    input,
+                    " ".repeat(column)
+                ),
+            )
+        })
+    }
+
+    pub fn eval(&self, rec: &FileRecord) -> bool {
+        self.0.eval(rec)
+    }
+}
+
+impl Node {
+    fn cost(&self) -> u8 {
+        match self {
+            Node::And(l, r) | Node::Or(l, r) => l.cost().max(r.cost()),
+            Node::Not(e) => e.cost(),
+            Node::Cond(attr, _) => attr.cost(),
+        }
+    }
+
+    fn eval(&self, rec: &FileRecord) -> bool {
+        match self {
+            Node::And(l, r) => l.eval(rec) && r.eval(rec),
+            Node::Or(l, r) => l.eval(rec) || r.eval(rec),
+            Node::Not(e) => !e.eval(rec),
+            Node::Cond(attr, test) => rec.test(*attr, test),
+        }
+    }
+}
+
+/// An entry as seen by `--where`, with the metadata the other filters use, so `--dereference`
+/// applies here as well
+pub struct FileRecord<'a> {
+    entry: &'a DirEntry,
+    depth: usize,
+    root: &'a Path,
+    meta: &'a Metadata,
+}
+
+impl<'a> FileRecord<'a> {
+    pub fn new(entry: &'a DirEntry, depth: usize, root: &'a Path, meta: &'a Metadata) -> Self {
+        FileRecord {
+            entry,
+            depth,
+            root,
+            meta,
+        }
+    }
+
+    fn kind(&self) -> FileKind {
+        FileKind::of(self.entry, self.meta)
+    }
+
+    fn name(&self) -> String {
+        self.entry.file_name().to_string_lossy().into_owned()
+    }
+
+    fn string(&self, attr: Attr) -> Option<String> {
+        Some(match attr {
+            Attr::Name => self.name(),
+            // relative to the search root, as `-r` matches by default
+            Attr::Path => self
+                .entry
+                .path()
+                .strip_prefix(self.root)
+                .unwrap_or(self.entry.path())
+                .to_string_lossy()
+                .into_owned(),
+            Attr::Ext => Path::new(self.entry.file_name())
+                .extension()?
+                .to_string_lossy()
+                .to_lowercase(),
+            Attr::Type => self.kind().label().to_string(),
+            Attr::Hash(algo) if self.meta.is_file() => {
+                hash_file(self.entry.path(), &[algo]).ok()?.remove(0)
+            }
+            _ => return None,
+        })
+    }
+
+    fn number(&self, attr: Attr) -> Option<i64> {
+        if attr == Attr::Depth {
+            return Some(self.depth as i64);
+        }
+        let meta = self.meta;
+        match attr {
+            Attr::Size => Some(meta.len() as i64),
+            Attr::Mode => Some((meta.mode() & 0o7777) as i64),
+            Attr::Owner => Some(meta.uid() as i64),
+            Attr::Group => Some(meta.gid() as i64),
+            Attr::Mtime => unix_secs(meta.modified()),
+            Attr::Atime => unix_secs(meta.accessed()),
+            Attr::Ctime => unix_secs(meta.created()),
+            _ => None,
+        }
+    }
+
+    fn equals(&self, attr: Attr, value: &Value) -> bool {
+        match value {
+            // `ext == fastq.gz` matches multi-part extensions
+            Value::Str(ext) if attr == Attr::Ext => has_ext(&self.name().to_lowercase(), ext),
+            Value::Str(s) => self.string(attr).is_some_and(|v| v == *s),
+            Value::Num(n) => self.number(attr) == Some(*n),
+            // `type == file` matches executables as well, as `-T f` does
+            Value::Kind(kind) => self.kind().is_any_of(&[*kind]),
+        }
+    }
+
+    fn test(&self, attr: Attr, test: &Test) -> bool {
+        match test {
+            Test::In(values) => values.iter().any(|v| self.equals(attr, v)),
+            Test::NotIn(values) => !values.iter().any(|v| self.equals(attr, v)),
+            Test::Regex(re, negate) => {
+                self.string(attr).is_some_and(|v| re.is_match(&v)) != *negate
+            }
+            Test::Cmp(op, n) => self.number(attr).is_some_and(|v| match op {
+                Op::Lt => v < *n,
+                Op::Le => v <= *n,
+                Op::Gt => v > *n,
+                Op::Ge => v >= *n,
+                _ => false,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let expr =
+            Expr::parse("(ext in [bam, cram] and size > 1G) or (name ~ 'tmp' and mtime < -30d)");
+        assert!(expr.is_ok());
+        // the name test is moved before the hash
+        let Expr(Node::And(left, _)) = Expr::parse("md5 == abc and name == a").unwrap() else {
+            panic!()
+        };
+        assert!(matches!(*left, Node::Cond(Attr::Name, _)));
+
+        let e = Expr::parse("size > 1x").unwrap_err().to_string();
+        assert!(e.contains("invalid value `1x`"), "{}", e);
+        assert!(e.ends_with("\n         ^"), "{}", e);
+        assert!(Expr::parse("sizes > 1").is_err());
+        assert!(Expr::parse("(name == a").is_err());
+        assert!(Expr::parse("name < a").is_err());
+        assert_eq!(parse_size("1.5k"), Some(1536));
+        assert_eq!(parse_size("2GiB"), Some(2 << 30));
+
+        // times are points in time, `-30d` is 30 days before now
+        let Expr(Node::Cond(_, Test::Cmp(op, n))) = Expr::parse("mtime < -30d").unwrap() else {
+            panic!()
+        };
+        assert_eq!(op, Op::Lt);
+        assert!((now() - 30 * 86400 - n).abs() <= 1);
+        let Expr(Node::Cond(_, Test::Cmp(op, _))) = Expr::parse("mtime < 2024-01-31").unwrap()
+        else {
+            panic!()
+        };
+        assert_eq!(op, Op::Lt);
+        assert!(Expr::parse("mtime < 30d").is_err());
+
+        // the type takes every `-T` kind
+        let Expr(Node::Cond(_, Test::In(values))) = Expr::parse("type in [fifo, x, L]").unwrap()
+        else {
+            panic!()
+        };
+        assert_eq!(
+            values,
+            vec![
+                Value::Kind(FileKind::Fifo),
+                Value::Kind(FileKind::Executable),
+                Value::Kind(FileKind::Broken)
+            ]
+        );
+        assert!(Expr::parse("type == other").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::{Error, ErrorKind};
+
+/// Extension groups, also used to color file names in the output
+pub const EXT_GROUPS: [(&str, &[&str]); 5] = [
+    ("archive", &["gz", "bz2", "zip", "tar", "xz", "lz4", "zst"]),
+    ("image", &["png", "jpeg", "jpg", "svg", "tiff", "bmp"]),
+    (
+        "document",
+        &["pdf", "html", "xml", "json", "tsv", "csv", "xlsx"],
+    ),
+    ("text", &["log", "txt", "md", "yaml", "yml", "toml", "ini"]),
+    (
+        "source",
+        &[
+            "rs", "go", "py", "pl", "java", "js", "ts", "c", "cpp", "sh", "bash", "zsh", "fish",
+            "r",
+        ],
+    ),
+];
+
+/// The group of a file name by its last extension, ignoring case
+pub fn group_of(name: &str) -> Option<&'static str> {
+    let (_, ext) = name.rsplit_once('.')?;
+    let ext = ext.to_lowercase();
+    EXT_GROUPS
+        .iter()
+        .find(|(_, exts)| exts.contains(&ext.as_str()))
+        .map(|(group, _)| *group)
+}
+
+/// Case-insensitive extension matching. An extension can span several parts (`fastq.gz`) and
+/// `@group` expands to the extensions of a group
+pub struct ExtFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl ExtFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, Error> {
+        Ok(ExtFilter {
+            include: expand(include)?,
+            exclude: expand(exclude)?,
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        (self.include.is_empty() || self.include.iter().any(|ext| has_ext(&name, ext)))
+            && !self.exclude.iter().any(|ext| has_ext(&name, ext))
+    }
+}
+
+// `name.ext`, a dot file like `.gz` has no extension
+pub fn has_ext(name: &str, ext: &str) -> bool {
+    name.len() > ext.len() + 1
+        && name.ends_with(ext)
+        && name[..name.len() - ext.len()].ends_with('.')
+}
+
+fn expand(exts: &[String]) -> Result<Vec<String>, Error> {
+    let mut out = vec![];
+    for ext in exts.iter().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+        if let Some(group) = ext.strip_prefix('@') {
+            let Some((_, members)) = EXT_GROUPS.iter().find(|(name, _)| *name == group) else {
+                let groups: Vec<String> =
+                    EXT_GROUPS.iter().map(|(g, _)| format!("@{}", g)).collect();
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "unknown extension group `{}`, expected one of: {}",
+                        ext,
+                        groups.join(", ")
+                    ),
+                ));
+            };
+            out.extend(members.iter().map(|e| e.to_string()));
+        } else {
+            out.push(ext.trim_start_matches('.').to_lowercase());
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ext_filter() {
+        let ext = |include: &[&str], exclude: &[&str]| {
+            let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            ExtFilter::new(&strings(include), &strings(exclude)).unwrap()
+        };
+        let filter = ext(&["csv", ".TSV", "fastq.gz"], &[]);
+        assert!(filter.is_match("a.csv"));
+        assert!(filter.is_match("b.tsv"));
+        assert!(filter.is_match("reads.R1.FASTQ.GZ"));
+        assert!(!filter.is_match("reads.gz"));
+        assert!(!filter.is_match("myfastq.gz"));
+        assert!(!ext(&["gz"], &[]).is_match(".gz"));
+
+        let filter = ext(&["@archive"], &["tar.gz"]);
+        assert!(filter.is_match("a.zip"));
+        assert!(!filter.is_match("a.tar.gz"));
+        assert!(ExtFilter::new(&["@nope".to_string()], &[]).is_err());
+        assert_eq!(group_of("main.RS"), Some("source"));
+    }
+}
@@ -0,0 +1,111 @@
+use clap::ValueEnum;
+use std::{
+    fs::{self, Metadata},
+    os::unix::fs::{FileTypeExt, MetadataExt},
+};
+use walkdir::DirEntry;
+
+/// Entry types for the Type column and `-T`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum FileKind {
+    /// regular file
+    #[value(name = "f", alias = "file")]
+    File,
+    /// directory
+    #[value(name = "d", alias = "dir")]
+    Dir,
+    /// symbolic link
+    #[value(name = "l", alias = "symlink")]
+    Symlink,
+    /// named pipe
+    #[value(name = "p", alias = "fifo")]
+    Fifo,
+    /// unix socket
+    #[value(name = "s", alias = "socket")]
+    Socket,
+    /// block device
+    #[value(name = "b", alias = "block")]
+    Block,
+    /// character device
+    #[value(name = "c", alias = "char")]
+    Char,
+    /// regular file with any execute bit set
+    #[value(name = "x", alias = "executable")]
+    Executable,
+    /// symbolic link whose target does not exist
+    #[value(name = "L", alias = "broken")]
+    Broken,
+}
+
+impl FileKind {
+    /// The most specific kind of an entry, `meta` is the metadata of the entry itself or of its
+    /// link target
+    pub fn of(entry: &DirEntry, meta: &Metadata) -> Self {
+        let ft = meta.file_type();
+        if ft.is_dir() {
+            FileKind::Dir
+        } else if ft.is_symlink() {
+            if fs::metadata(entry.path()).is_err() {
+                FileKind::Broken
+            } else {
+                FileKind::Symlink
+            }
+        } else if ft.is_fifo() {
+            FileKind::Fifo
+        } else if ft.is_socket() {
+            FileKind::Socket
+        } else if ft.is_block_device() {
+            FileKind::Block
+        } else if ft.is_char_device() {
+            FileKind::Char
+        } else if meta.mode() & 0o111 != 0 {
+            FileKind::Executable
+        } else {
+            FileKind::File
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileKind::File => "file",
+            FileKind::Dir => "dir",
+            FileKind::Symlink => "symlink",
+            FileKind::Fifo => "fifo",
+            FileKind::Socket => "socket",
+            FileKind::Block => "block",
+            FileKind::Char => "char",
+            FileKind::Executable => "executable",
+            FileKind::Broken => "broken",
+        }
+    }
+
+    // executables are files and broken links are symlinks as well
+    fn parent(&self) -> Option<Self> {
+        match self {
+            FileKind::Executable => Some(FileKind::File),
+            FileKind::Broken => Some(FileKind::Symlink),
+            _ => None,
+        }
+    }
+
+    /// Whether an entry of this kind passes `-T`, an empty list allows every kind
+    pub fn is_any_of(&self, kinds: &[FileKind]) -> bool {
+        kinds.is_empty()
+            || kinds.contains(self)
+            || self.parent().is_some_and(|p| kinds.contains(&p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_any_of() {
+        assert!(FileKind::Executable.is_any_of(&[FileKind::File]));
+        assert!(!FileKind::File.is_any_of(&[FileKind::Executable]));
+        assert!(FileKind::Broken.is_any_of(&[FileKind::Dir, FileKind::Symlink]));
+        assert!(FileKind::Fifo.is_any_of(&[]));
+        assert!(!FileKind::Socket.is_any_of(&[FileKind::Fifo, FileKind::Block]));
+    }
+}
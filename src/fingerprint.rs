@@ -0,0 +1,154 @@
+use log::info;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, BufWriter, Error, Write},
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Path, PathBuf},
+};
+use tabwriter::TabWriter;
+use walkdir::WalkDir;
+
+use crate::{
+    cli::FingerprintOpt,
+    hash::{HashAlgo, Hasher},
+    process::is_hidden,
+};
+
+/// Compute a hash for every directory under `root` from the name, type, mode and content hash
+/// of its children, so equal trees get equal hashes. Returns the hashes keyed by relative path
+pub fn fingerprint_tree(
+    root: &Path,
+    show_hidden: bool,
+    hasher: &Hasher,
+) -> Result<BTreeMap<PathBuf, String>, Error> {
+    // children come before their parent dir, so every dir is complete when it is reached
+    let mut entries = vec![];
+    for entry in WalkDir::new(root)
+        .contents_first(true)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|e| !is_hidden(e) || show_hidden)
+    {
+        entries.push(entry?);
+    }
+
+    let files: Vec<PathBuf> = entries
+        .iter()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    let digests: HashMap<PathBuf, String> = files
+        .iter()
+        .cloned()
+        .zip(
+            hasher
+                .hash_files(&files)
+                .into_iter()
+                .map(|mut d| d.remove(0)),
+        )
+        .collect();
+
+    let mut children: HashMap<PathBuf, blake3::Hasher> = HashMap::new();
+    let mut dirs = BTreeMap::new();
+    for entry in entries.iter() {
+        let path = entry.path();
+        let meta = entry.metadata()?;
+        let (kind, digest) = if entry.file_type().is_dir() {
+            let hash = children
+                .remove(path)
+                .unwrap_or_default()
+                .finalize()
+                .to_hex()
+                .to_string();
+            let rel = path.strip_prefix(root).unwrap_or(path);
+            let rel = if rel.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                rel
+            };
+            dirs.insert(rel.to_path_buf(), hash.clone());
+            ("d", hash)
+        } else if entry.file_type().is_file() {
+            ("f", digests.get(path).cloned().unwrap_or_default())
+        } else if entry.file_type().is_symlink() {
+            let target = fs::read_link(path)?;
+            let hash = blake3::hash(target.as_os_str().as_bytes());
+            ("l", hash.to_hex().to_string())
+        } else {
+            ("o", String::new())
+        };
+
+        if entry.depth() == 0 {
+            continue;
+        }
+        let parent = path.parent().unwrap_or(root).to_path_buf();
+        let record = children.entry(parent).or_default();
+        record.update(kind.as_bytes());
+        record.update(format!("\0{:o}\0", meta.permissions().mode() & 0o7777).as_bytes());
+        record.update(entry.file_name().as_bytes());
+        record.update(b"\0");
+        record.update(digest.as_bytes());
+        record.update(b"\n");
+    }
+
+    Ok(dirs)
+}
+
+pub fn fingerprint(opt: &FingerprintOpt, jobs: usize) -> Result<bool, Error> {
+    let root = Path::new(opt.rootdir.as_deref().unwrap_or("."));
+    if !root.is_dir() {
+        eprintln!("error: dir `{}` not exists", root.display());
+        return Ok(false);
+    }
+    let hasher = Hasher::new(&[HashAlgo::Blake3], None, jobs, !opt.no_hash_cache);
+    let dirs = fingerprint_tree(root, opt.show_hiden, &hasher)?;
+    hasher.save_cache()?;
+
+    let mut fp: Box<dyn Write> = if let Some(out) = opt.out.as_ref() {
+        Box::new(BufWriter::new(File::create(out)?))
+    } else {
+        Box::new(TabWriter::new(BufWriter::new(io::stdout())))
+    };
+    if !opt.header {
+        writeln!(fp, "Hash\tPath")?;
+    }
+    for (rel, hash) in dirs.iter() {
+        let depth = if rel == Path::new(".") {
+            0
+        } else {
+            rel.components().count()
+        };
+        if depth <= opt.depth {
+            writeln!(fp, "{}\t{}", hash, rel.display())?;
+        }
+    }
+    fp.flush()?;
+
+    info!("{} dir(s) fingerprinted", dirs.len());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_fingerprint_tree() {
+        let base = TempDir::new("fingerprint");
+        for tree in ["a", "b"] {
+            fs::create_dir_all(base.join(tree).join("sub")).unwrap();
+            fs::write(base.join(tree).join("sub").join("x.txt"), b"x").unwrap();
+        }
+        let hasher = Hasher::new(&[HashAlgo::Blake3], None, 1, false);
+        let a = fingerprint_tree(&base.join("a"), false, &hasher).unwrap();
+        let b = fingerprint_tree(&base.join("b"), false, &hasher).unwrap();
+        assert_eq!(a, b);
+
+        fs::write(base.join("b").join("sub").join("x.txt"), b"y").unwrap();
+        let b = fingerprint_tree(&base.join("b"), false, &hasher).unwrap();
+        assert_ne!(a[Path::new(".")], b[Path::new(".")]);
+        assert_ne!(a[Path::new("sub")], b[Path::new("sub")]);
+    }
+}
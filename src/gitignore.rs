@@ -0,0 +1,205 @@
+use ignore::{
+    Match,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
+use log::{trace, warn};
+use std::{
+    collections::HashMap,
+    env,
+    io::Error,
+    path::{self, Path, PathBuf},
+};
+use walkdir::DirEntry;
+
+/// Rules from `.ignore` and `.gitignore` files, `.git/info/exclude`, the global ignore files and
+/// custom ignore files, with gitignore semantics. Deeper files take precedence over shallower ones.
+/// As in git, `.gitignore` files only apply inside a git repository, `.ignore` files everywhere
+pub struct IgnoreFilter {
+    src: PathBuf,
+    root: PathBuf,
+    in_repo: bool,
+    // matchers of the directories above the root up to the git repository root, nearest first
+    parents: Vec<Vec<Gitignore>>,
+    // `.git/info/exclude`, global and custom ignore files, in order of precedence
+    extra: Vec<Gitignore>,
+    dirs: HashMap<PathBuf, Vec<Gitignore>>,
+}
+
+impl IgnoreFilter {
+    pub fn new<P: AsRef<Path>>(src: P, ignore_files: &[String]) -> Result<Self, Error> {
+        let root = path::absolute(src.as_ref())?;
+
+        // rules of parent directories only apply inside a git repository
+        let mut parents = vec![];
+        let mut repo = root.join(".git").exists().then(|| root.clone());
+        if repo.is_none() {
+            let mut dirs = vec![];
+            for dir in root.ancestors().skip(1) {
+                dirs.push(dir);
+                if dir.join(".git").exists() {
+                    repo = Some(dir.to_path_buf());
+                    parents = dirs.iter().map(|d| load_dir(d, true)).collect();
+                    break;
+                }
+            }
+        }
+
+        let in_repo = repo.is_some();
+        let mut extra = vec![];
+        if let Some(repo) = repo {
+            trace!("git repository: {}", repo.display());
+            extra.push(load_file(&repo, &repo.join(".git/info/exclude")));
+        }
+        let (global, err) = Gitignore::global();
+        if let Some(err) = err {
+            warn!("global gitignore: {}", err);
+        }
+        extra.push(global);
+        if let Some(config) = env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+        {
+            extra.push(load_file(&root, &config.join("fdx").join("ignore")));
+        }
+        for file in ignore_files {
+            if !Path::new(file).is_file() {
+                warn!("ignore file `{}` not exists", file);
+            }
+            extra.push(load_file(&root, Path::new(file)));
+        }
+
+        Ok(IgnoreFilter {
+            src: src.as_ref().to_path_buf(),
+            root,
+            in_repo,
+            parents,
+            extra,
+            dirs: HashMap::new(),
+        })
+    }
+
+    /// Whether the entry is ignored, an ignored directory is not descended into
+    pub fn is_ignored(&mut self, entry: &DirEntry) -> bool {
+        if entry.depth() == 0 {
+            return false;
+        }
+        let rel = entry.path().strip_prefix(&self.src).unwrap_or(entry.path());
+        let path = self.root.join(rel);
+        let is_dir = entry.file_type().is_dir();
+
+        let mut dir = path.parent();
+        while let Some(d) = dir {
+            if !d.starts_with(&self.root) {
+                break;
+            }
+            let matchers = self.dirs.entry(d.to_path_buf()).or_insert_with(|| {
+                // a repository may also start below the search root
+                let in_repo = self.in_repo
+                    || d.ancestors()
+                        .take_while(|a| a.starts_with(&self.root))
+                        .any(|a| a.join(".git").exists());
+                load_dir(d, in_repo)
+            });
+            if let Some(ignored) = decide(matchers, &path, is_dir) {
+                return ignored;
+            }
+            dir = d.parent();
+        }
+        for matchers in self.parents.iter().chain([&self.extra]) {
+            if let Some(ignored) = decide(matchers, &path, is_dir) {
+                return ignored;
+            }
+        }
+        false
+    }
+}
+
+// the first matcher with a matching pattern decides, `!pattern` whitelists
+fn decide(matchers: &[Gitignore], path: &Path, is_dir: bool) -> Option<bool> {
+    for m in matchers {
+        match m.matched(path, is_dir) {
+            Match::Ignore(_) => return Some(true),
+            Match::Whitelist(_) => return Some(false),
+            Match::None => {}
+        }
+    }
+    None
+}
+
+// ignore files of a directory, `.ignore` takes precedence over `.gitignore`
+fn load_dir(dir: &Path, in_repo: bool) -> Vec<Gitignore> {
+    let files: &[&str] = if in_repo {
+        &[".ignore", ".gitignore"]
+    } else {
+        &[".ignore"]
+    };
+    files
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|file| file.is_file())
+        .map(|file| load_file(dir, &file))
+        .collect()
+}
+
+// patterns in `file` are matched relative to `root`
+fn load_file(root: &Path, file: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    if file.is_file() {
+        if let Some(err) = builder.add(file) {
+            warn!("{}: {}", file.display(), err);
+        }
+    }
+    builder.build().unwrap_or_else(|err| {
+        warn!("{}: {}", file.display(), err);
+        Gitignore::empty()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+    use std::fs;
+    use walkdir::WalkDir;
+
+    #[test]
+    fn test_ignore_filter() {
+        let base = TempDir::new("ignore");
+        fs::create_dir_all(base.join("build").join("out")).unwrap();
+        fs::create_dir_all(base.join("src")).unwrap();
+        fs::write(base.join(".gitignore"), "/build/\n*.log\n").unwrap();
+        fs::write(base.join("src").join(".ignore"), "!keep.log\n").unwrap();
+        for file in [
+            "build/out/a.o",
+            "a.log",
+            "src/b.log",
+            "src/keep.log",
+            "src/main.rs",
+        ] {
+            fs::write(base.join(file), b"").unwrap();
+        }
+
+        let found = || {
+            let mut filter = IgnoreFilter::new(&base, &[]).unwrap();
+            let mut found: Vec<String> = WalkDir::new(&base)
+                .into_iter()
+                .filter_entry(|e| !filter.is_ignored(e))
+                .map(|e| e.unwrap())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.path().strip_prefix(&base).unwrap().display().to_string())
+                .collect();
+            found.sort();
+            found
+        };
+        // outside a git repository only `.ignore` applies
+        let outside = found();
+        fs::create_dir(base.join(".git")).unwrap();
+        let inside = found();
+        assert_eq!(outside.len(), 7);
+        assert_eq!(
+            inside,
+            [".gitignore", "src/.ignore", "src/keep.log", "src/main.rs"]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::{
+    ffi::OsStr,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+/// A set of glob patterns. A pattern with a `/` is matched against the path relative to the
+/// search root, otherwise against the file name. `*` does not cross directories, `**` does
+pub struct Globs {
+    name: GlobSet,
+    path: GlobSet,
+    len: usize,
+}
+
+impl Globs {
+    pub fn new(patterns: &[String]) -> Result<Self, Error> {
+        let (mut name, mut path) = (GlobSetBuilder::new(), GlobSetBuilder::new());
+        for pattern in patterns {
+            let glob = |p: &str| {
+                GlobBuilder::new(p)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))
+            };
+            // a leading `/` anchors the pattern at the root, relative paths never start with one
+            match pattern.strip_prefix('/') {
+                Some(anchored) => path.add(glob(anchored)?),
+                None if pattern.contains('/') => path.add(glob(pattern)?),
+                None => name.add(glob(pattern)?),
+            };
+        }
+        let build = |b: GlobSetBuilder| {
+            b.build()
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))
+        };
+        Ok(Globs {
+            name: build(name)?,
+            path: build(path)?,
+            len: patterns.len(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_match(&self, rel: &Path, name: &OsStr) -> bool {
+        self.name.is_match(name) || self.path.is_match(rel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_globs() {
+        let globs = Globs::new(&["*.rs".to_string(), "src/**/mod.rs".to_string()]).unwrap();
+        let matched = |rel: &str| {
+            let rel = Path::new(rel);
+            globs.is_match(rel, rel.file_name().unwrap())
+        };
+        assert!(matched("a/b/main.rs"));
+        assert!(matched("src/x/y/mod.rs"));
+        assert!(!matched("lib/x/mod.txt"));
+
+        let anchored = Globs::new(&["/build".to_string(), "docs/*.md".to_string()]).unwrap();
+        assert!(anchored.is_match(Path::new("build"), OsStr::new("build")));
+        assert!(!anchored.is_match(Path::new("src/build"), OsStr::new("build")));
+        assert!(!anchored.is_match(Path::new("docs/api/x.md"), OsStr::new("x.md")));
+        assert!(Globs::new(&["[".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use clap::ValueEnum;
+use log::{debug, warn};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Error, Read, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{content::par_map, temp::create_temp};
+
+// digests kept in the cache file, the ones used by the last run are kept first
+const CACHE_MAX_ENTRIES: usize = 100_000;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HashAlgo {
+    Sha256,
+    Blake3,
+    /// XXH3 64-bit, fast but not cryptographic
+    #[value(alias = "xxhash")]
+    Xxh3,
+    Md5,
+}
+
+impl HashAlgo {
+    pub fn header(&self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "SHA256",
+            HashAlgo::Blake3 => "BLAKE3",
+            HashAlgo::Xxh3 => "XXH3",
+            HashAlgo::Md5 => "MD5",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Blake3 => "blake3",
+            HashAlgo::Xxh3 => "xxh3",
+            HashAlgo::Md5 => "md5",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        HashAlgo::value_variants()
+            .iter()
+            .find(|algo| algo.name() == name)
+            .copied()
+    }
+}
+
+enum State {
+    Sha256(Box<Sha256>),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
+    Md5(Md5),
+}
+
+impl State {
+    fn new(algo: HashAlgo) -> Self {
+        match algo {
+            HashAlgo::Sha256 => State::Sha256(Box::default()),
+            HashAlgo::Blake3 => State::Blake3(Box::default()),
+            HashAlgo::Xxh3 => State::Xxh3(Box::default()),
+            HashAlgo::Md5 => State::Md5(Md5::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            State::Sha256(h) => h.update(data),
+            State::Blake3(h) => {
+                h.update(data);
+            }
+            State::Xxh3(h) => h.update(data),
+            State::Md5(h) => h.update(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            State::Sha256(h) => to_hex(&h.finalize()),
+            State::Blake3(h) => h.finalize().to_hex().to_string(),
+            State::Xxh3(h) => format!("{:016x}", h.digest()),
+            State::Md5(h) => to_hex(&h.finalize()),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash a file with several algorithms in a single pass
+pub fn hash_file(path: &Path, algos: &[HashAlgo]) -> Result<Vec<String>, Error> {
+    let mut states: Vec<State> = algos.iter().map(|a| State::new(*a)).collect();
+    let mut fp = File::open(path)?;
+    let mut buf = vec![0u8; 256 * 1024];
+    loop {
+        let n = fp.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        states.iter_mut().for_each(|s| s.update(&buf[..n]));
+    }
+    Ok(states.into_iter().map(|s| s.finish()).collect())
+}
+
+// one cached digest per algorithm and inode, it is replaced once the file changes
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+    algo: HashAlgo,
+    dev: u64,
+    ino: u64,
+}
+
+impl CacheKey {
+    fn new(algo: HashAlgo, meta: &fs::Metadata) -> Self {
+        CacheKey {
+            algo,
+            dev: meta.dev(),
+            ino: meta.ino(),
+        }
+    }
+}
+
+// a cached digest is valid as long as the size and mtime of the file are unchanged
+struct CacheEntry {
+    size: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+    digest: String,
+    // looked up or added by this run
+    used: bool,
+    // line in the cache file, entries not used by this run keep their order
+    order: usize,
+}
+
+impl CacheEntry {
+    fn new(meta: &fs::Metadata, digest: String) -> Self {
+        CacheEntry {
+            size: meta.size(),
+            mtime: meta.mtime(),
+            mtime_nsec: meta.mtime_nsec(),
+            digest,
+            used: true,
+            order: 0,
+        }
+    }
+
+    fn is_valid(&self, meta: &fs::Metadata) -> bool {
+        self.size == meta.size()
+            && self.mtime == meta.mtime()
+            && self.mtime_nsec == meta.mtime_nsec()
+    }
+}
+
+pub struct Hasher {
+    algos: Vec<HashAlgo>,
+    max_size: Option<u64>,
+    jobs: usize,
+    cache: Option<PathBuf>,
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    dirty: Mutex<bool>,
+}
+
+impl Hasher {
+    pub fn new(algos: &[HashAlgo], max_size: Option<u64>, jobs: usize, use_cache: bool) -> Self {
+        let mut uniq: Vec<HashAlgo> = vec![];
+        for algo in algos {
+            if !uniq.contains(algo) {
+                uniq.push(*algo);
+            }
+        }
+        let cache = if use_cache { cache_path() } else { None };
+        let entries = cache.as_ref().map(|p| load_cache(p)).unwrap_or_default();
+        Hasher {
+            algos: uniq,
+            max_size,
+            jobs,
+            cache,
+            entries: Mutex::new(entries),
+            dirty: Mutex::new(false),
+        }
+    }
+
+    pub fn headers(&self) -> Vec<&'static str> {
+        self.algos.iter().map(|a| a.header()).collect()
+    }
+
+    /// Digests of one file for every algorithm, `-` if the file is skipped or can not be read
+    pub fn digests(&self, path: &Path) -> Vec<String> {
+        let skipped = vec!["-".to_string(); self.algos.len()];
+        let meta = match fs::metadata(path) {
+            Ok(meta) if meta.is_file() => meta,
+            _ => return skipped,
+        };
+        if self.max_size.is_some_and(|max| meta.len() > max) {
+            debug!("skip hashing large file `{}`", path.display());
+            return skipped;
+        }
+
+        let keys: Vec<CacheKey> = self
+            .algos
+            .iter()
+            .map(|a| CacheKey::new(*a, &meta))
+            .collect();
+        {
+            let mut entries = self.entries.lock().unwrap();
+            let mut cached = vec![];
+            for key in keys.iter() {
+                if let Some(entry) = entries.get_mut(key).filter(|e| e.is_valid(&meta)) {
+                    entry.used = true;
+                    cached.push(entry.digest.clone());
+                }
+            }
+            if cached.len() == keys.len() {
+                return cached;
+            }
+        }
+
+        match hash_file(path, &self.algos) {
+            Ok(digests) => {
+                if self.cache.is_some() {
+                    let mut entries = self.entries.lock().unwrap();
+                    for (key, digest) in keys.into_iter().zip(digests.iter()) {
+                        entries.insert(key, CacheEntry::new(&meta, digest.clone()));
+                    }
+                    *self.dirty.lock().unwrap() = true;
+                }
+                digests
+            }
+            Err(e) => {
+                warn!("failed to hash `{}`: {}", path.display(), e);
+                skipped
+            }
+        }
+    }
+
+    /// Hash files in parallel, results are in the same order as `paths`
+    pub fn hash_files(&self, paths: &[PathBuf]) -> Vec<Vec<String>> {
+        par_map(paths, self.jobs, |path| self.digests(path))
+    }
+
+    /// Write new digests back to the cache file, at most `CACHE_MAX_ENTRIES` of them
+    pub fn save_cache(&self) -> Result<(), Error> {
+        let Some(cache) = self.cache.as_ref() else {
+            return Ok(());
+        };
+        if !*self.dirty.lock().unwrap() {
+            return Ok(());
+        }
+        if let Some(dir) = cache.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // write to a temporary file first, so concurrent runs never see a partial cache
+        let (tmp, fp) = create_temp(cache)?;
+        let entries = self.entries.lock().unwrap();
+        let res = write_cache(&mut BufWriter::new(fp), &entries, CACHE_MAX_ENTRIES)
+            .and_then(|_| fs::rename(&tmp, cache));
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        res
+    }
+}
+
+// entries used by this run first, then the others in their previous order up to `max`
+fn write_cache(
+    fp: &mut dyn Write,
+    entries: &HashMap<CacheKey, CacheEntry>,
+    max: usize,
+) -> Result<(), Error> {
+    let mut sorted: Vec<_> = entries.iter().collect();
+    sorted.sort_by_key(|(_, e)| (!e.used, e.order));
+    if sorted.len() > max {
+        debug!(
+            "drop {} least recently used cached digest(s)",
+            sorted.len() - max
+        );
+        sorted.truncate(max);
+    }
+    for (k, e) in sorted {
+        writeln!(
+            fp,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            k.algo.name(),
+            k.dev,
+            k.ino,
+            e.size,
+            e.mtime,
+            e.mtime_nsec,
+            e.digest
+        )?;
+    }
+    fp.flush()
+}
+
+fn cache_path() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))
+        .map(|dir| dir.join("fdx").join("hash-cache.tsv"))
+}
+
+fn load_cache(path: &Path) -> HashMap<CacheKey, CacheEntry> {
+    let mut entries = HashMap::new();
+    let Ok(fp) = File::open(path) else {
+        return entries;
+    };
+    for (order, line) in BufReader::new(fp).lines().map_while(Result::ok).enumerate() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            continue;
+        }
+        let (Some(algo), Ok(dev), Ok(ino), Ok(size), Ok(mtime), Ok(mtime_nsec)) = (
+            HashAlgo::from_name(fields[0]),
+            fields[1].parse(),
+            fields[2].parse(),
+            fields[3].parse(),
+            fields[4].parse(),
+            fields[5].parse(),
+        ) else {
+            continue;
+        };
+        let entry = CacheEntry {
+            size,
+            mtime,
+            mtime_nsec,
+            digest: fields[6].to_string(),
+            used: false,
+            order,
+        };
+        // an inode listed twice keeps its first, most recent digest
+        entries.entry(CacheKey { algo, dev, ino }).or_insert(entry);
+    }
+    debug!("load {} cached digest(s)", entries.len());
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_hash_file() {
+        let dir = TempDir::new("hash");
+        let path = dir.join("abc");
+        fs::write(&path, b"abc").unwrap();
+        let digests = hash_file(&path, &[HashAlgo::Sha256, HashAlgo::Md5]).unwrap();
+        assert_eq!(
+            digests[0],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(digests[1], "900150983cd24fb0d6963f7d28e17f72");
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = TempDir::new("hash-cache");
+        let (a, b, cache) = (dir.join("a"), dir.join("b"), dir.join("hash-cache.tsv"));
+        fs::write(&a, "abc").unwrap();
+        fs::write(&b, "de").unwrap();
+        let load = || {
+            let mut hasher = Hasher::new(&[HashAlgo::Md5], None, 1, false);
+            hasher.cache = Some(cache.clone());
+            *hasher.entries.lock().unwrap() = load_cache(&cache);
+            hasher
+        };
+        let save = |hasher: &Hasher, max| {
+            let mut out = vec![];
+            write_cache(&mut out, &hasher.entries.lock().unwrap(), max).unwrap();
+            fs::write(&cache, out).unwrap();
+        };
+
+        let hasher = load();
+        hasher.hash_files(&[a.clone(), b.clone()]);
+        save(&hasher, 10);
+        assert_eq!(load_cache(&cache).len(), 2);
+
+        // a changed file replaces its stale entry, and the entry not used by the run is dropped
+        fs::write(&a, "abcd").unwrap();
+        let hasher = load();
+        assert_eq!(hasher.digests(&a), ["e2fc714c4727ee9395f324cd2e7f331f"]);
+        save(&hasher, 1);
+        let entries = load_cache(&cache);
+        assert_eq!(entries.len(), 1);
+        let entry = entries.values().next().unwrap();
+        assert_eq!(entry.size, 4);
+        assert_eq!(entry.digest, "e2fc714c4727ee9395f324cd2e7f331f");
+    }
+}
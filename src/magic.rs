@@ -0,0 +1,323 @@
+use clap::ValueEnum;
+use flate2::read::GzDecoder;
+use log::debug;
+use std::{
+    fs::{self, File},
+    io::{self, Error, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::content::{Codec, par_map, regular_file};
+
+// enough for the magic bytes of every kind and for telling text from binary
+const PROBE_SIZE: u64 = 8 * 1024;
+// HDF5 files may start with a user block, the signature is then at one of these offsets
+const HDF5_OFFSETS: [usize; 4] = [0, 512, 1024, 2048];
+
+/// Content types detected from the leading bytes of a file
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Elf,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    Zip,
+    Pdf,
+    Png,
+    Jpeg,
+    Sqlite,
+    Hdf5,
+    Bam,
+    Cram,
+    /// ASCII text
+    Text,
+    /// UTF-8 text with non-ASCII characters
+    Utf8,
+    Binary,
+    /// zero-byte file
+    Empty,
+}
+
+impl Kind {
+    /// The kind of the first bytes of a file
+    pub fn detect(head: &[u8]) -> Self {
+        if head.is_empty() {
+            Kind::Empty
+        } else if head.starts_with(b"\x7fELF") {
+            Kind::Elf
+        } else if head.starts_with(&[0x1f, 0x8b]) {
+            if is_bam(head) { Kind::Bam } else { Kind::Gzip }
+        } else if head.starts_with(b"BZh") {
+            Kind::Bzip2
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Kind::Xz
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Kind::Zstd
+        } else if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Kind::Zip
+        } else if head.starts_with(b"%PDF-") {
+            Kind::Pdf
+        } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Kind::Png
+        } else if head.starts_with(&[0xff, 0xd8, 0xff]) {
+            Kind::Jpeg
+        } else if head.starts_with(b"SQLite format 3\0") {
+            Kind::Sqlite
+        } else if HDF5_OFFSETS.iter().any(|off| {
+            head.get(*off..)
+                .is_some_and(|h| h.starts_with(b"\x89HDF\r\n\x1a\n"))
+        }) {
+            Kind::Hdf5
+        } else if head.starts_with(b"CRAM") {
+            Kind::Cram
+        } else if head.contains(&0) {
+            Kind::Binary
+        } else if head.is_ascii() {
+            Kind::Text
+        } else {
+            match std::str::from_utf8(head) {
+                Ok(_) => Kind::Utf8,
+                // a character cut off at the end of the probe
+                Err(e) if e.error_len().is_none() => Kind::Utf8,
+                Err(_) => Kind::Binary,
+            }
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Kind::Elf => "elf",
+            Kind::Gzip => "gzip",
+            Kind::Bzip2 => "bzip2",
+            Kind::Xz => "xz",
+            Kind::Zstd => "zstd",
+            Kind::Zip => "zip",
+            Kind::Pdf => "pdf",
+            Kind::Png => "png",
+            Kind::Jpeg => "jpeg",
+            Kind::Sqlite => "sqlite",
+            Kind::Hdf5 => "hdf5",
+            Kind::Bam => "bam",
+            Kind::Cram => "cram",
+            Kind::Text => "text",
+            Kind::Utf8 => "utf8",
+            Kind::Binary => "binary",
+            Kind::Empty => "empty",
+        }
+    }
+
+    // UTF-8 text is text as well
+    fn is_any_of(&self, kinds: &[Kind]) -> bool {
+        kinds.contains(self) || *self == Kind::Utf8 && kinds.contains(&Kind::Text)
+    }
+}
+
+// BAM is BGZF, gzip blocks with a `BC` extra field, and starts with `BAM\1` once decoded
+fn is_bam(head: &[u8]) -> bool {
+    if head.len() < 18 || head[3] & 0x04 == 0 || &head[12..14] != b"BC" {
+        return false;
+    }
+    let mut magic = vec![];
+    GzDecoder::new(head).take(4).read_to_end(&mut magic).is_ok() && magic == b"BAM\x01"
+}
+
+// kinds a file with one of these extensions is expected to have, empty text files are fine
+const EXPECTED: [(&[&str], &[Kind]); 14] = [
+    (&["gz", "tgz", "bgz"], &[Kind::Gzip]),
+    (&["bz2", "tbz2"], &[Kind::Bzip2]),
+    (&["xz", "txz"], &[Kind::Xz]),
+    (&["zst", "tzst"], &[Kind::Zstd]),
+    (
+        &["zip", "jar", "apk", "docx", "xlsx", "pptx", "odt", "ods"],
+        &[Kind::Zip],
+    ),
+    (&["pdf"], &[Kind::Pdf]),
+    (&["png"], &[Kind::Png]),
+    (&["jpg", "jpeg"], &[Kind::Jpeg]),
+    (&["sqlite", "sqlite3"], &[Kind::Sqlite]),
+    (&["h5", "hdf5", "he5"], &[Kind::Hdf5]),
+    (&["bam"], &[Kind::Bam]),
+    (&["cram"], &[Kind::Cram]),
+    (&["so"], &[Kind::Elf]),
+    (
+        &[
+            "txt", "log", "md", "csv", "tsv", "json", "xml", "html", "yaml", "yml", "toml", "ini",
+            "fa", "fasta", "fq", "fastq", "sam", "vcf", "bed", "gff", "gtf", "rs", "py", "sh",
+        ],
+        &[Kind::Text, Kind::Empty],
+    ),
+];
+
+/// The detected kind of a file, and why it looks mislabeled or corrupt with `--kind-mismatch`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sniffed {
+    pub kind: Option<Kind>,
+    pub mismatch: Option<String>,
+}
+
+/// `--show-kind`, `--kind` and `--kind-mismatch`
+pub struct Sniffer {
+    kinds: Vec<Kind>,
+    show_kind: bool,
+    mismatch: bool,
+    // compressed files are only decoded up to this size with `--kind-mismatch`
+    max_size: u64,
+    jobs: usize,
+}
+
+impl Sniffer {
+    pub fn new(
+        kinds: &[Kind],
+        show_kind: bool,
+        mismatch: bool,
+        max_size: u64,
+        jobs: usize,
+    ) -> Self {
+        Sniffer {
+            kinds: kinds.to_vec(),
+            show_kind,
+            mismatch,
+            max_size,
+            jobs,
+        }
+    }
+
+    pub fn headers(&self) -> Vec<&'static str> {
+        let mut headers = vec![];
+        if self.show_kind {
+            headers.push("Kind");
+        }
+        if self.mismatch {
+            headers.push("Mismatch");
+        }
+        headers
+    }
+
+    /// The kind of a regular file, directories and other entries have none
+    pub fn sniff(&self, path: &Path) -> Sniffed {
+        if !regular_file(path).is_ok_and(|meta| meta.is_some()) {
+            return Sniffed::default();
+        }
+        let mut head = vec![];
+        let read = File::open(path).and_then(|file| file.take(PROBE_SIZE).read_to_end(&mut head));
+        if read.is_err() {
+            return Sniffed::default();
+        }
+        let kind = Kind::detect(&head);
+        let mismatch = if self.mismatch {
+            self.find_mismatch(path, kind, &head)
+        } else {
+            None
+        };
+        Sniffed {
+            kind: Some(kind),
+            mismatch,
+        }
+    }
+
+    /// Sniff files in parallel, results are in the same order as `paths`
+    pub fn sniff_files(&self, paths: &[PathBuf]) -> Vec<Sniffed> {
+        par_map(paths, self.jobs, |path| self.sniff(path))
+    }
+
+    pub fn is_match(&self, sniffed: &Sniffed) -> bool {
+        (self.kinds.is_empty() || sniffed.kind.is_some_and(|k| k.is_any_of(&self.kinds)))
+            && (!self.mismatch || sniffed.mismatch.is_some())
+    }
+
+    /// The Kind and Mismatch columns, each preceded by a tab
+    pub fn columns(&self, sniffed: &Sniffed) -> String {
+        let mut out = String::new();
+        if self.show_kind {
+            out.push('\t');
+            out.push_str(sniffed.kind.map_or("-", |k| k.label()));
+        }
+        if self.mismatch {
+            out.push('\t');
+            out.push_str(sniffed.mismatch.as_deref().unwrap_or("-"));
+        }
+        out
+    }
+
+    // an extension promising another kind, or a compressed stream that does not decode to the
+    // end. Streams are decoded up to `max_size`, larger compressed files are not decoded at all
+    fn find_mismatch(&self, path: &Path, kind: Kind, head: &[u8]) -> Option<String> {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if let Some((_, expected)) = EXPECTED
+            .iter()
+            .find(|(exts, _)| exts.contains(&ext.as_str()))
+        {
+            if !kind.is_any_of(expected) {
+                return Some(format!(".{} file contains {}", ext, kind.label()));
+            }
+        }
+        let codec = Codec::detect(head)?;
+        if fs::metadata(path).is_ok_and(|meta| meta.len() > self.max_size) {
+            debug!("skip decoding large file `{}`", path.display());
+            return None;
+        }
+        let decoded = File::open(path)
+            .and_then(|file| codec.decoder(file))
+            .and_then(|decoder| io::copy(&mut decoder.take(self.max_size), &mut io::sink()));
+        decoded
+            .err()
+            .map(|e: Error| format!("corrupt {}: {}", kind.label(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+    use std::io::Write;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Kind::detect(b""), Kind::Empty);
+        assert_eq!(Kind::detect(b"\x7fELF\x02\x01"), Kind::Elf);
+        assert_eq!(Kind::detect(b"%PDF-1.7\n"), Kind::Pdf);
+        assert_eq!(Kind::detect(b"plain text\n"), Kind::Text);
+        assert_eq!(Kind::detect("naïve\n".as_bytes()), Kind::Utf8);
+        assert_eq!(Kind::detect(&"é".as_bytes()[..1]), Kind::Utf8);
+        assert_eq!(Kind::detect(b"\xff\xfe\x00a"), Kind::Binary);
+        let mut hdf5 = vec![0u8; 512];
+        hdf5.extend(b"\x89HDF\r\n\x1a\n");
+        assert_eq!(Kind::detect(&hdf5), Kind::Hdf5);
+
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+        gz.write_all(b"hello\n").unwrap();
+        let gz = gz.finish().unwrap();
+        assert_eq!(Kind::detect(&gz), Kind::Gzip);
+
+        let sniffer = Sniffer::new(&[], true, true, u64::MAX, 1);
+        let dir = TempDir::new("magic");
+        std::fs::write(dir.join("ok.gz"), &gz).unwrap();
+        std::fs::write(dir.join("cut.gz"), &gz[..gz.len() - 4]).unwrap();
+        std::fs::write(dir.join("plain.gz"), b"not compressed\n").unwrap();
+        let sniffed = |name: &str| sniffer.sniff(&dir.join(name));
+        assert_eq!(sniffed("ok.gz").mismatch, None);
+        assert!(
+            sniffed("cut.gz")
+                .mismatch
+                .unwrap()
+                .starts_with("corrupt gzip")
+        );
+        assert_eq!(
+            sniffed("plain.gz").mismatch.unwrap(),
+            ".gz file contains text"
+        );
+        assert_eq!(sniffer.sniff(&dir), Sniffed::default());
+        // too large to be decoded
+        let small = Sniffer::new(&[], true, true, 8, 1);
+        assert_eq!(small.sniff(&dir.join("cut.gz")).mismatch, None);
+        // a fifo without a writer is skipped instead of blocking
+        let fifo = std::ffi::CString::new(dir.join("p").into_os_string().into_encoded_bytes());
+        assert_eq!(unsafe { libc::mkfifo(fifo.unwrap().as_ptr(), 0o600) }, 0);
+        assert_eq!(sniffed("p"), Sniffed::default());
+        assert!(Kind::Utf8.is_any_of(&[Kind::Text]));
+    }
+}
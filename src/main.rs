@@ -1,18 +1,55 @@
 use std::io::Error;
 
+use archive::archive_entries;
 use clap::Parser;
-use cli::Opt;
+use cli::{Command, ManifestCommand, Opt, OutFormat};
+use content::{ContentFilter, Reader};
+use delete::{DeleteOptions, delete_entries};
+use exec::{CommandTemplate, exec_batch, exec_each};
+use hash::Hasher;
 use log::info;
+use magic::Sniffer;
+use pattern::RegexFilter;
 use process::search_dir;
+use report::{ReportOptions, report_entries};
+use std::path::{Path, PathBuf};
+use transfer::{Mode, transfer_entries};
 
+mod archive;
 mod cli;
+mod compare;
+mod content;
+mod delete;
+mod dupes;
+mod empty;
+mod exec;
+mod expr;
+mod ext;
+mod filetype;
+mod fingerprint;
+mod gitignore;
+mod glob;
+mod hash;
+mod magic;
+mod manifest;
+mod owner;
+mod pattern;
 mod process;
+mod rename;
+mod report;
+mod temp;
+mod transfer;
 
 fn main() {
     let now = std::time::Instant::now();
     match run_main() {
-        Ok(_) => {}
-        Err(_) => {
+        Ok(true) => {}
+        Ok(false) => {
+            info!("Elapsed: {:?}", now.elapsed());
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
             std::process::exit(1);
         }
     }
@@ -30,13 +67,31 @@ fn log_level(verbose: u8) -> log::LevelFilter {
     }
 }
 
-fn run_main() -> Result<(), Error> {
+// number of parallel jobs, defaults to the number of CPU cores
+fn jobs(jobs: Option<usize>) -> usize {
+    jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    })
+}
+
+fn run_main() -> Result<bool, Error> {
     let opt = Opt::parse();
 
     // Set up logging
+    let level = log_level(opt.verbose);
+    // the gitignore crates log every file they open at debug level, only show that with trace
+    let deps = if level == log::LevelFilter::Trace {
+        level
+    } else {
+        level.min(log::LevelFilter::Warn)
+    };
     let mut logger = env_logger::Builder::new();
     logger
-        .filter(None, log_level(opt.verbose))
+        .filter(None, level)
+        .filter(Some("ignore"), deps)
+        .filter(Some("globset"), deps)
         .format_timestamp(Some(env_logger::fmt::TimestampPrecision::Seconds))
         .format_module_path(false)
         .format_target(true)
@@ -44,31 +99,176 @@ fn run_main() -> Result<(), Error> {
         .format_level(true)
         .init();
 
+    if let Some(cmd) = opt.command.as_ref() {
+        return match cmd {
+            Command::Compare(compare_opt) => compare::compare(compare_opt),
+            Command::Dupes(dupes_opt) => dupes::find_dupes(dupes_opt),
+            Command::Fingerprint(fp_opt) => fingerprint::fingerprint(fp_opt, jobs(fp_opt.jobs)),
+            Command::Manifest(manifest_opt) => match &manifest_opt.command {
+                ManifestCommand::Create(create_opt) => {
+                    manifest::create(create_opt, jobs(create_opt.jobs))
+                }
+                ManifestCommand::Verify(verify_opt) => {
+                    manifest::verify(verify_opt, jobs(verify_opt.jobs))
+                }
+            },
+            Command::Rename(rename_opt) => rename::rename(rename_opt),
+        };
+    }
+
     // if opt.rootdir is None, use default value "."
     let dir = opt.rootdir.unwrap_or_else(|| ".".to_string());
 
-    search_dir(
-        dir,
-        opt.regex.as_deref(),
-        opt.ignore_case,
+    let report = opt.format != OutFormat::Table || opt.group_by.is_some();
+    let list = opt.exec.is_none() && opt.exec_batch.is_none() && !report;
+    let jobs = jobs(opt.jobs);
+    let hasher = if opt.hash.is_empty() || !list {
+        None
+    } else {
+        Some(Hasher::new(
+            &opt.hash,
+            opt.hash_max_size,
+            jobs,
+            !opt.no_hash_cache,
+        ))
+    };
+    let sniffer = (!opt.kind.is_empty() || opt.kind_mismatch || opt.show_kind && list).then(|| {
+        Sniffer::new(
+            &opt.kind,
+            opt.show_kind && list,
+            opt.kind_mismatch,
+            opt.contains_max_size,
+            jobs,
+        )
+    });
+    let reader = Reader::new(opt.contains_max_size, !opt.no_decompress, jobs);
+    let content = opt
+        .contains
+        .as_deref()
+        .map(|pattern| {
+            ContentFilter::new(
+                pattern,
+                opt.filter.ignore_case,
+                reader,
+                opt.show_matches && list,
+                opt.context,
+            )
+        })
+        .transpose()?;
+    let matched = search_dir(
+        &dir,
+        &opt.filter.regex,
+        &opt.filter.not_regex,
+        opt.filter.all_regex,
+        opt.filter.match_on,
+        opt.filter.ignore_case,
         opt.show_all,
-        opt.deepth,
+        opt.filter.deepth,
         opt.show_type,
         opt.show_size,
-        opt.file_size_max,
-        opt.file_size_min,
+        opt.filter.file_size_max,
+        opt.filter.file_size_min,
         &opt.size_fmt,
         opt.created_time,
-        opt.filetype.as_ref(),
-        opt.ext.as_deref(),
+        &opt.filetype,
+        &opt.filter.ext,
+        &opt.filter.exclude_ext,
+        opt.filter.where_expr.as_deref(),
+        opt.filter.owner.as_deref(),
+        opt.filter.group.as_deref(),
+        opt.filter.nouser,
+        opt.filter.nogroup,
+        opt.filter.perm.as_deref(),
+        opt.filter.empty || opt.delete_empty,
+        opt.filter.empty_dirs_recursive || opt.delete_empty,
         opt.name,
-        opt.depth,
+        // directories can only be removed after their contents
+        opt.depth || opt.delete_dirs || opt.delete_empty,
         opt.full_path,
-        opt.show_link_dir,
-        opt.show_hiden,
+        opt.filter.show_link_dir,
+        opt.filter.show_hiden,
+        opt.link_target,
+        opt.dereference,
+        opt.ignore.no_ignore,
+        &opt.ignore.ignore_file,
+        &opt.filter.glob,
+        &opt.filter.exclude,
+        &opt.filter.prune,
         opt.header,
         opt.out.as_ref(),
+        content.as_ref(),
+        sniffer.as_ref(),
+        (opt.lines && list).then_some(&reader),
+        hasher.as_ref(),
+        list,
     )?;
 
-    Ok(())
+    // run commands on matched entries or report them instead of listing them
+    if report {
+        let regex = RegexFilter::new(
+            &opt.filter.regex,
+            &opt.filter.not_regex,
+            opt.filter.all_regex,
+            opt.filter.match_on,
+            opt.filter.ignore_case,
+        )?;
+        let report_opts = ReportOptions {
+            format: opt.format,
+            group_by: opt.group_by.as_deref(),
+            no_header: opt.header,
+            out: opt.out.as_ref(),
+            size_fmt: &opt.size_fmt,
+        };
+        return report_entries(Path::new(&dir), &matched, &regex, &report_opts);
+    }
+    if opt.exec.is_some() || opt.exec_batch.is_some() {
+        let regex = RegexFilter::new(
+            &opt.filter.regex,
+            &opt.filter.not_regex,
+            opt.filter.all_regex,
+            opt.filter.match_on,
+            opt.filter.ignore_case,
+        )?;
+        let captures = Some((regex, PathBuf::from(&dir)));
+        if let Some(cmd) = opt.exec.as_ref() {
+            return exec_each(&CommandTemplate::new(cmd, captures), &matched, jobs);
+        }
+        if let Some(cmd) = opt.exec_batch.as_ref() {
+            return exec_batch(&CommandTemplate::new(cmd, captures), &matched, jobs);
+        }
+    }
+    if opt.delete || opt.delete_empty {
+        let delete_opts = DeleteOptions {
+            dry_run: opt.dry_run,
+            yes: opt.yes,
+            remove_dirs: opt.delete_dirs || opt.delete_empty,
+            trash: opt.trash,
+            audit_log: opt.audit_log.as_ref(),
+            size_fmt: &opt.size_fmt,
+        };
+        return delete_entries(&matched, &delete_opts);
+    }
+    if let Some(out) = opt.archive.as_ref() {
+        return archive_entries(Path::new(&dir), &matched, Path::new(out), &opt.size_fmt);
+    }
+    if let Some(dest) = opt.copy_to.as_ref() {
+        return transfer_entries(
+            Path::new(&dir),
+            &matched,
+            Path::new(dest),
+            Mode::Copy,
+            opt.conflict,
+        );
+    }
+    if let Some(dest) = opt.move_to.as_ref() {
+        return transfer_entries(
+            Path::new(&dir),
+            &matched,
+            Path::new(dest),
+            Mode::Move,
+            opt.conflict,
+        );
+    }
+
+    Ok(true)
 }
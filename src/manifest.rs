@@ -0,0 +1,320 @@
+//! Manifest files list the path, size, mtime and checksum of every file under a root.
+//!
+//! Two formats are supported:
+//!
+//! * `fdx`: a header line `# fdx manifest v1 algo=<algo>` followed by one tab separated line per
+//!   file: `path  size  mtime  hash`, where `path` is relative to the root and `mtime` is in
+//!   seconds since the unix epoch
+//! * `sum`: the `<hash>  <path>` lines of `sha256sum`, `md5sum` or `b3sum`, so
+//!   `cd <root> && sha256sum -c MANIFEST` works as well
+//!
+//! In the `fdx` format a backslash, tab or newline in a path is escaped as `\\`, `\t` or `\n`.
+//! The `sum` format follows coreutils: only backslash and newline are escaped and such a line
+//! starts with a backslash.
+
+use clap::ValueEnum;
+use log::{info, warn};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    cli::{FilterOpts, ManifestCreateOpt, ManifestVerifyOpt},
+    filetype::FileKind,
+    hash::{HashAlgo, Hasher},
+    process::collect_entries,
+};
+
+const HEADER: &str = "# fdx manifest v1";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ManifestFormat {
+    /// path, size, mtime and hash separated by tabs
+    Fdx,
+    /// `<hash>  <path>`, compatible with `sha256sum -c`
+    Sum,
+}
+
+struct Record {
+    size: Option<u64>,
+    mtime: Option<u64>,
+    hash: String,
+}
+
+pub fn escape(path: &str) -> String {
+    path.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn escape_sum(path: &str) -> String {
+    path.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+pub fn unescape(path: &str) -> String {
+    let mut out = String::new();
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// matched files under `root` as relative paths, the manifest file itself is left out
+fn scan(
+    root: &Path,
+    filter: &FilterOpts,
+    skip: Option<&Path>,
+) -> Result<Vec<(String, PathBuf)>, Error> {
+    let skip = skip.and_then(|p| fs::canonicalize(p).ok());
+    let mut files = vec![];
+    for path in collect_entries(root, filter, None, &[FileKind::File])? {
+        if skip.is_some() && fs::canonicalize(&path).ok() == skip {
+            continue;
+        }
+        let rel = path.strip_prefix(root).unwrap_or(&path);
+        files.push((rel.to_string_lossy().into_owned(), path));
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn mtime(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn create(opt: &ManifestCreateOpt, jobs: usize) -> Result<bool, Error> {
+    let root = Path::new(opt.rootdir.as_deref().unwrap_or("."));
+    let files = scan(root, &opt.filter, opt.out.as_deref().map(Path::new))?;
+    let hasher = Hasher::new(&[opt.hash], None, jobs, !opt.no_hash_cache);
+    let paths: Vec<PathBuf> = files.iter().map(|(_, p)| p.clone()).collect();
+    let digests = hasher.hash_files(&paths);
+    hasher.save_cache()?;
+
+    let mut fp: Box<dyn Write> = if let Some(out) = opt.out.as_ref() {
+        Box::new(BufWriter::new(File::create(out)?))
+    } else {
+        Box::new(BufWriter::new(io::stdout()))
+    };
+    let (mut success, mut written) = (true, 0usize);
+    if opt.format == ManifestFormat::Fdx {
+        writeln!(fp, "{} algo={}", HEADER, opt.hash.name())?;
+    }
+    for ((rel, path), digest) in files.iter().zip(digests) {
+        if digest[0] == "-" {
+            // the file vanished or is unreadable, it is left out rather than written with no hash
+            success = false;
+            continue;
+        }
+        match opt.format {
+            ManifestFormat::Fdx => {
+                let meta = match fs::metadata(path) {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        warn!("failed to read `{}`: {}", path.display(), e);
+                        success = false;
+                        continue;
+                    }
+                };
+                writeln!(
+                    fp,
+                    "{}\t{}\t{}\t{}",
+                    escape(rel),
+                    meta.len(),
+                    mtime(&meta),
+                    digest[0]
+                )?;
+            }
+            ManifestFormat::Sum => {
+                let escaped = escape_sum(rel);
+                let prefix = if escaped != *rel { "\\" } else { "" };
+                writeln!(fp, "{}{}  {}", prefix, digest[0], escaped)?;
+            }
+        }
+        written += 1;
+    }
+    fp.flush()?;
+
+    info!("{} file(s) written to manifest", written);
+    Ok(success)
+}
+
+fn parse_manifest(
+    path: &Path,
+    default_algo: HashAlgo,
+) -> Result<(HashAlgo, BTreeMap<String, Record>), Error> {
+    let invalid = |line_no: usize| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid manifest line {} in `{}`", line_no, path.display()),
+        )
+    };
+
+    let mut algo = default_algo;
+    let mut is_fdx = false;
+    let mut records = BTreeMap::new();
+    for (idx, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if let Some(header) = line.strip_prefix(HEADER) {
+            if let Some(name) = header.trim().strip_prefix("algo=") {
+                algo = HashAlgo::from_str(name, true).map_err(|_| invalid(idx + 1))?;
+            }
+            is_fdx = true;
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if is_fdx {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return Err(invalid(idx + 1));
+            }
+            let (Ok(size), Ok(mtime)) = (fields[1].parse(), fields[2].parse()) else {
+                return Err(invalid(idx + 1));
+            };
+            let record = Record {
+                size: Some(size),
+                mtime: Some(mtime),
+                hash: fields[3].to_string(),
+            };
+            records.insert(unescape(fields[0]), record);
+        } else {
+            let (escaped, line) = match line.strip_prefix('\\') {
+                Some(line) => (true, line),
+                None => (false, line.as_str()),
+            };
+            let (hash, name) = line.split_once(' ').ok_or_else(|| invalid(idx + 1))?;
+            // `sha256sum -b` marks binary mode with `*` instead of a space
+            let name = name
+                .strip_prefix(' ')
+                .or_else(|| name.strip_prefix('*'))
+                .ok_or_else(|| invalid(idx + 1))?;
+            let record = Record {
+                size: None,
+                mtime: None,
+                hash: hash.to_string(),
+            };
+            let name = if escaped {
+                unescape(name)
+            } else {
+                name.to_string()
+            };
+            records.insert(name, record);
+        }
+    }
+    Ok((algo, records))
+}
+
+pub fn verify(opt: &ManifestVerifyOpt, jobs: usize) -> Result<bool, Error> {
+    let manifest = Path::new(&opt.manifest);
+    let (algo, records) = parse_manifest(manifest, opt.hash)?;
+    let root = match opt.root.as_ref() {
+        Some(root) => PathBuf::from(root),
+        None => manifest
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+
+    let files = scan(&root, &opt.filter, Some(manifest))?;
+    let present: BTreeSet<&str> = files.iter().map(|(rel, _)| rel.as_str()).collect();
+
+    // only files listed in the manifest are hashed, size mismatches do not need a hash
+    let mut to_hash = vec![];
+    let mut report: Vec<(&str, &str)> = vec![];
+    let mut unreadable = 0usize;
+    for (rel, record) in records.iter() {
+        if !present.contains(rel.as_str()) {
+            report.push(("MISSING", rel));
+            continue;
+        }
+        let meta = match fs::metadata(root.join(rel)) {
+            Ok(meta) => meta,
+            Err(e) => {
+                warn!("failed to read `{}`: {}", rel, e);
+                unreadable += 1;
+                continue;
+            }
+        };
+        if record.size.is_some_and(|size| size != meta.len()) {
+            report.push(("MODIFIED", rel));
+            continue;
+        }
+        to_hash.push((rel, record, mtime(&meta)));
+    }
+    for (rel, _) in files.iter() {
+        if !records.contains_key(rel) {
+            report.push(("EXTRA", rel));
+        }
+    }
+
+    let hasher = Hasher::new(&[algo], None, jobs, !opt.no_hash_cache);
+    let paths: Vec<PathBuf> = to_hash.iter().map(|(rel, _, _)| root.join(rel)).collect();
+    let digests = hasher.hash_files(&paths);
+    hasher.save_cache()?;
+    let mut ok = 0usize;
+    for ((rel, record, mtime), digest) in to_hash.into_iter().zip(digests) {
+        if !digest[0].eq_ignore_ascii_case(&record.hash) {
+            report.push(("MODIFIED", rel));
+        } else if record.mtime.is_some_and(|t| t != mtime) {
+            report.push(("TOUCHED", rel));
+        } else {
+            ok += 1;
+        }
+    }
+
+    let mut fp = BufWriter::new(io::stdout());
+    report.sort_by(|a, b| a.1.cmp(b.1));
+    for (status, rel) in report.iter() {
+        writeln!(fp, "{}\t{}", status, escape(rel))?;
+    }
+    fp.flush()?;
+
+    let count = |s: &str| report.iter().filter(|(status, _)| *status == s).count();
+    let (missing, extra, modified) = (count("MISSING"), count("EXTRA"), count("MODIFIED"));
+    info!(
+        "ok: {}, missing: {}, extra: {}, modified: {}, mtime changed: {}",
+        ok,
+        missing,
+        extra,
+        modified,
+        count("TOUCHED")
+    );
+    if missing + extra + modified > 0 {
+        warn!("manifest `{}` does not match", manifest.display());
+    }
+    Ok(missing + extra + modified + unreadable == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let name = "a\tb\\c\nd";
+        assert_eq!(escape(name), "a\\tb\\\\c\\nd");
+        assert_eq!(unescape(&escape(name)), name);
+        assert_eq!(unescape("plain/path.txt"), "plain/path.txt");
+    }
+}
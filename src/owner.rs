@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    io::{Error, ErrorKind},
+    os::unix::fs::MetadataExt,
+    path::Path,
+    sync::OnceLock,
+};
+
+// names and ids of the local passwd or group database
+#[derive(Default)]
+struct IdDb {
+    by_name: HashMap<String, u32>,
+    by_id: HashMap<u32, String>,
+}
+
+impl IdDb {
+    // `name:password:id:...` lines, as in `/etc/passwd` and `/etc/group`
+    fn load(path: &Path) -> Self {
+        let mut db = IdDb::default();
+        let content = fs::read_to_string(path).unwrap_or_default();
+        for line in content.lines().filter(|l| !l.starts_with('#')) {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 3 {
+                continue;
+            }
+            if let Ok(id) = fields[2].parse() {
+                db.by_name.insert(fields[0].to_string(), id);
+                db.by_id.entry(id).or_insert_with(|| fields[0].to_string());
+            }
+        }
+        db
+    }
+
+    // a name or a numeric id
+    fn resolve(&self, value: &str, kind: &str) -> Result<u32, Error> {
+        if let Some(id) = self.by_name.get(value) {
+            return Ok(*id);
+        }
+        value.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown {} `{}`", kind, value),
+            )
+        })
+    }
+}
+
+// the databases are read once per run
+fn users() -> &'static IdDb {
+    static USERS: OnceLock<IdDb> = OnceLock::new();
+    USERS.get_or_init(|| IdDb::load(Path::new("/etc/passwd")))
+}
+
+fn groups() -> &'static IdDb {
+    static GROUPS: OnceLock<IdDb> = OnceLock::new();
+    GROUPS.get_or_init(|| IdDb::load(Path::new("/etc/group")))
+}
+
+/// A user name or uid
+pub fn resolve_user(value: &str) -> Result<u32, Error> {
+    users().resolve(value, "user")
+}
+
+/// A group name or gid
+pub fn resolve_group(value: &str) -> Result<u32, Error> {
+    groups().resolve(value, "group")
+}
+
+#[derive(Debug, PartialEq)]
+enum PermMatch {
+    Exact,
+    All,
+    Any,
+}
+
+/// `--perm` as in find: `MODE` exact, `-MODE` all bits set, `/MODE` any bit set
+#[derive(Debug, PartialEq)]
+pub struct Perm {
+    mode: u32,
+    kind: PermMatch,
+}
+
+impl Perm {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "invalid permission `{}`, expected an octal mode like 644 or symbolic like u+x,o+w",
+                    value
+                ),
+            )
+        };
+        let (kind, mode) = match value.chars().next() {
+            Some('-') => (PermMatch::All, &value[1..]),
+            Some('/') => (PermMatch::Any, &value[1..]),
+            _ => (PermMatch::Exact, value),
+        };
+        if mode.is_empty() {
+            return Err(invalid());
+        }
+        let mode = if mode.chars().all(|c| c.is_digit(8)) {
+            u32::from_str_radix(mode, 8)
+                .ok()
+                .filter(|m| *m <= 0o7777)
+                .ok_or_else(invalid)?
+        } else {
+            symbolic_mode(mode).ok_or_else(invalid)?
+        };
+        Ok(Perm { mode, kind })
+    }
+
+    pub fn is_match(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match self.kind {
+            PermMatch::Exact => mode == self.mode,
+            PermMatch::All => mode & self.mode == self.mode,
+            PermMatch::Any => self.mode == 0 || mode & self.mode != 0,
+        }
+    }
+}
+
+// `u+x,go=r` -> the bits named by the clauses, starting from no bits
+fn symbolic_mode(value: &str) -> Option<u32> {
+    let mut mode = 0u32;
+    for clause in value.split(',') {
+        let op = clause.find(['+', '='])?;
+        let (who, perms) = (&clause[..op], &clause[op + 1..]);
+        let mut mask = 0u32;
+        for c in who.chars() {
+            mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => return None,
+            };
+        }
+        if who.is_empty() {
+            mask = 0o7777;
+        }
+        let mut bits = 0u32;
+        for c in perms.chars() {
+            bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => return None,
+            };
+        }
+        mode |= bits & mask;
+    }
+    Some(mode)
+}
+
+/// `--owner`, `--group`, `--nouser`, `--nogroup` and `--perm`
+pub struct OwnerFilter {
+    uid: Option<u32>,
+    gid: Option<u32>,
+    nouser: bool,
+    nogroup: bool,
+    perm: Option<Perm>,
+}
+
+impl OwnerFilter {
+    pub fn new(
+        owner: Option<&str>,
+        group: Option<&str>,
+        nouser: bool,
+        nogroup: bool,
+        perm: Option<&str>,
+    ) -> Result<Self, Error> {
+        Ok(OwnerFilter {
+            uid: owner.map(resolve_user).transpose()?,
+            gid: group.map(resolve_group).transpose()?,
+            nouser,
+            nogroup,
+            perm: perm.map(Perm::parse).transpose()?,
+        })
+    }
+
+    pub fn is_match(&self, meta: &Metadata) -> bool {
+        self.uid.is_none_or(|uid| meta.uid() == uid)
+            && self.gid.is_none_or(|gid| meta.gid() == gid)
+            && (!self.nouser || !users().by_id.contains_key(&meta.uid()))
+            && (!self.nogroup || !groups().by_id.contains_key(&meta.gid()))
+            && self
+                .perm
+                .as_ref()
+                .is_none_or(|perm| perm.is_match(meta.mode()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_perm() {
+        assert!(Perm::parse("644").unwrap().is_match(0o100644));
+        assert!(!Perm::parse("644").unwrap().is_match(0o100755));
+        assert!(Perm::parse("-u+x,o+w").unwrap().is_match(0o777));
+        assert!(!Perm::parse("-u+x,o+w").unwrap().is_match(0o755));
+        assert!(Perm::parse("/022").unwrap().is_match(0o620));
+        assert!(!Perm::parse("/022").unwrap().is_match(0o600));
+        assert_eq!(
+            Perm::parse("u=rw,go=r").unwrap(),
+            Perm::parse("644").unwrap()
+        );
+        assert!(Perm::parse("u+q").is_err());
+        assert!(Perm::parse("8").is_err());
+
+        let dir = TempDir::new("passwd");
+        let path = dir.join("passwd");
+        fs::write(
+            &path,
+            "# comment\nroot:x:0:0::/root:/bin/sh\nalice:x:1000:1000::/:\n",
+        )
+        .unwrap();
+        let db = IdDb::load(&path);
+        assert_eq!(db.resolve("alice", "user").unwrap(), 1000);
+        assert_eq!(db.resolve("42", "user").unwrap(), 42);
+        assert!(db.resolve("bob", "user").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use std::{
+    io::{Error, ErrorKind},
+    path::{self, Path},
+};
+use walkdir::DirEntry;
+
+/// The part of an entry that `-r` and `--not-regex` are matched against
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum MatchOn {
+    /// the file name
+    Name,
+    /// the path relative to the search root
+    Relpath,
+    /// the absolute path
+    Abspath,
+}
+
+/// Include and exclude regexes. Included entries match any (or all) of the `-r` patterns and
+/// none of the `--not-regex` patterns
+pub struct RegexFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    all: bool,
+    on: MatchOn,
+    names: Vec<String>,
+}
+
+fn build(patterns: &[String], ignore_case: bool) -> Result<Vec<Regex>, Error> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("invalid regex `{}`: {}", pattern, e),
+                    )
+                })
+        })
+        .collect()
+}
+
+impl RegexFilter {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        all: bool,
+        on: MatchOn,
+        ignore_case: bool,
+    ) -> Result<Self, Error> {
+        let include = build(include, ignore_case)?;
+        let mut names: Vec<String> = vec![];
+        for name in include.iter().flat_map(|re| re.capture_names().flatten()) {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+        Ok(RegexFilter {
+            include,
+            exclude: build(exclude, ignore_case)?,
+            all,
+            on,
+            names,
+        })
+    }
+
+    fn target(&self, path: &Path, root: &Path) -> String {
+        match self.on {
+            MatchOn::Name => path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            MatchOn::Relpath => path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned(),
+            MatchOn::Abspath => path::absolute(path)
+                .unwrap_or_else(|_| path.to_path_buf())
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+
+    pub fn is_match(&self, entry: &DirEntry, root: &Path) -> bool {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return true;
+        }
+        let target = self.target(entry.path(), root);
+        let included = if self.all {
+            self.include.iter().all(|re| re.is_match(&target))
+        } else {
+            self.include.is_empty() || self.include.iter().any(|re| re.is_match(&target))
+        };
+        included && !self.exclude.iter().any(|re| re.is_match(&target))
+    }
+
+    /// Names of the named capture groups in the `-r` patterns, without duplicates
+    pub fn capture_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Values of the named capture groups, in the order of `capture_names`. A group is taken from
+    /// the first pattern that defines it and matches, unmatched groups are empty
+    pub fn captures(&self, path: &Path, root: &Path) -> Vec<String> {
+        let target = self.target(path, root);
+        let caps: Vec<_> = self
+            .include
+            .iter()
+            .filter_map(|re| re.captures(&target))
+            .collect();
+        self.names
+            .iter()
+            .map(|name| {
+                caps.iter()
+                    .find_map(|c| c.name(name))
+                    .map(|m| m.as_str().to_string())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+    use walkdir::WalkDir;
+
+    #[test]
+    fn test_regex_filter() {
+        let base = TempDir::new("pattern");
+        std::fs::create_dir_all(base.join("logs")).unwrap();
+        std::fs::write(base.join("logs").join("run.log"), b"").unwrap();
+        let entry = WalkDir::new(base.join("logs").join("run.log"))
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let filter = |include: &[&str], exclude: &[&str], all: bool, on: MatchOn| {
+            RegexFilter::new(&strings(include), &strings(exclude), all, on, false).unwrap()
+        };
+        assert!(filter(&["^logs/"], &[], false, MatchOn::Relpath).is_match(&entry, &base));
+        assert!(!filter(&["^logs/"], &[], false, MatchOn::Name).is_match(&entry, &base));
+        assert!(filter(&["^/", "run"], &[], true, MatchOn::Abspath).is_match(&entry, &base));
+        assert!(!filter(&["^/", "nope"], &[], true, MatchOn::Abspath).is_match(&entry, &base));
+        assert!(!filter(&["run"], &["\\.log$"], false, MatchOn::Name).is_match(&entry, &base));
+        assert!(RegexFilter::new(&strings(&["("]), &[], false, MatchOn::Name, false).is_err());
+
+        let filter = filter(
+            &[r"(?P<sample>[^_]+)_S\d+_(?P<lane>L\d+)", "(?P<read>R[12])"],
+            &[],
+            false,
+            MatchOn::Name,
+        );
+        assert_eq!(filter.capture_names(), ["sample", "lane", "read"]);
+        let path = Path::new("fq/NA12878_S12_L001_R1_001.fastq.gz");
+        assert_eq!(
+            filter.captures(path, Path::new("fq")),
+            ["NA12878", "L001", "R1"]
+        );
+    }
+}
@@ -1,8 +1,7 @@
-use log::info;
-use regex::RegexBuilder;
+use log::{info, warn};
 use std::{
     env,
-    fs::File,
+    fs::{self, File, Metadata},
     io::{self, BufWriter, Error, Write},
     path::{Path, PathBuf},
     time::SystemTime,
@@ -10,6 +9,21 @@ use std::{
 use tabwriter::TabWriter;
 use walkdir::{self, DirEntry, WalkDir};
 
+use crate::{
+    cli::{FilterOpts, IgnoreOpts},
+    content::{ContentFilter, Line, Reader},
+    empty::EmptyFilter,
+    expr::{Expr, FileRecord},
+    ext::{ExtFilter, group_of},
+    filetype::FileKind,
+    gitignore::IgnoreFilter,
+    glob::Globs,
+    hash::Hasher,
+    magic::Sniffer,
+    owner::OwnerFilter,
+    pattern::{MatchOn, RegexFilter},
+};
+
 //  define ANSI color codes
 const COLOR_RESET: &str = "\x1b[0m";
 const COLOR_RED: &str = "\x1b[31m";
@@ -20,11 +34,13 @@ const COLOR_PINK: &str = "\x1b[35m";
 const COLOR_CYAN: &str = "\x1b[36m";
 const COLOR_WHITE: &str = "\x1b[37m";
 
-
 #[allow(clippy::too_many_arguments)]
 pub fn search_dir<P>(
     src: P,
-    regex_pattern: Option<&str>,
+    regexes: &[String],
+    not_regexes: &[String],
+    regex_all: bool,
+    match_on: MatchOn,
     ignore_case: bool,
     all: bool,
     depth: usize,
@@ -34,16 +50,37 @@ pub fn search_dir<P>(
     size_limit_min: Option<u64>,
     size_fmt: &str,
     created_time: bool,
-    filter_type: Option<&String>,
-    extension: Option<&str>,
+    filter_type: &[FileKind],
+    extensions: &[String],
+    exclude_exts: &[String],
+    where_expr: Option<&str>,
+    owner: Option<&str>,
+    group: Option<&str>,
+    nouser: bool,
+    nogroup: bool,
+    perm: Option<&str>,
+    empty: bool,
+    empty_dirs: bool,
     show_file_name: bool,
     depth_first: bool,
     full_path: bool,
     show_link_dir: bool,
     show_hiden: bool,
+    link_target: bool,
+    dereference: bool,
+    no_ignore: bool,
+    ignore_files: &[String],
+    globs: &[String],
+    excludes: &[String],
+    prune: &[String],
     no_header: bool,
     outfile: Option<&String>,
-) -> Result<(), Error>
+    content: Option<&ContentFilter>,
+    sniffer: Option<&Sniffer>,
+    line_counter: Option<&Reader>,
+    hasher: Option<&Hasher>,
+    list: bool,
+) -> Result<Vec<PathBuf>, Error>
 where
     P: AsRef<Path> + Clone,
 {
@@ -52,21 +89,43 @@ where
         std::process::exit(1);
     }
 
-    let mut fp: Box<dyn Write> = if let Some(out) = outfile {
+    let mut fp: Box<dyn Write> = if !list {
+        Box::new(io::sink())
+    } else if let Some(out) = outfile {
         Box::new(File::create(out)?)
     } else {
         Box::new(BufWriter::new(io::stdout()))
     };
 
-    let regex = regex_pattern.map(|pattern| {
-        RegexBuilder::new(pattern)
-            .case_insensitive(ignore_case)
-            .build()
-            .unwrap()
-    });
+    let regex = RegexFilter::new(regexes, not_regexes, regex_all, match_on, ignore_case)?;
+
+    let mut ignore = if no_ignore {
+        None
+    } else {
+        Some(IgnoreFilter::new(&src, ignore_files)?)
+    };
+
+    let (globs, excludes) = (Globs::new(globs)?, Globs::new(excludes)?);
+    let ext_filter = ExtFilter::new(extensions, exclude_exts)?;
+    let where_expr = where_expr.map(Expr::parse).transpose()?;
+    let owner_filter = OwnerFilter::new(owner, group, nouser, nogroup, perm)?;
+    let mut empty_filter = EmptyFilter::new(empty, empty_dirs);
+    let root = src.as_ref().to_path_buf();
+    let relative = |e: &DirEntry| {
+        e.path()
+            .strip_prefix(&root)
+            .unwrap_or(e.path())
+            .to_path_buf()
+    };
+    let skipped = |e: &DirEntry| {
+        e.depth() > 0
+            && (e.file_type().is_dir() && prune.iter().any(|name| e.file_name() == name.as_str())
+                || !excludes.is_empty() && excludes.is_match(&relative(e), e.file_name()))
+    };
 
     let is_tty = outfile.is_none();
     let mut item_count = 0usize;
+    let mut link_loops = 0usize;
     let mut tw = TabWriter::new(vec![]);
 
     let (mut show_type, mut show_size, mut created_time, mut show_file_name, mut full_path) = (
@@ -85,7 +144,7 @@ where
         full_path = true;
     }
     // header info
-    if !no_header {
+    if !no_header && list {
         let mut header = Vec::new();
         if show_type {
             header.push("Type");
@@ -100,6 +159,19 @@ where
             header.push("Name");
         }
         header.push("Path");
+        header.extend(regex.capture_names().iter().map(String::as_str));
+        if link_target {
+            header.extend(["Target", "Resolved"]);
+        }
+        if let Some(sniffer) = sniffer {
+            header.extend(sniffer.headers());
+        }
+        if line_counter.is_some() {
+            header.push("Lines");
+        }
+        if let Some(hasher) = hasher {
+            header.extend(hasher.headers());
+        }
         let header_join = header.join("\t") + "\n";
 
         if is_tty {
@@ -109,6 +181,8 @@ where
         }
     }
     let mut vec_all: Vec<String> = vec![];
+    let mut matched: Vec<PathBuf> = vec![];
+    let mut pending: Vec<String> = vec![];
     for entry in WalkDir::new(src)
         .min_depth(0)
         .max_depth(depth)
@@ -116,34 +190,46 @@ where
         .sort_by(|a, b| a.file_name().cmp(b.file_name()))
         .follow_links(show_link_dir)
         .into_iter()
-        .filter_entry(|e| !is_hidden(e) || show_hiden)
+        .filter_entry(|e| {
+            (!is_hidden(e) || show_hiden)
+                && !skipped(e)
+                && !ignore.as_mut().is_some_and(|i| i.is_ignored(e))
+        })
     {
-        let rec = entry?;
+        let (rec, depth) = match entry {
+            Ok(rec) => {
+                let depth = rec.depth();
+                (rec, depth)
+            }
+            Err(e) => match unfollowed_link(&e) {
+                Some((rec, problem)) => {
+                    if let Some(problem) = problem {
+                        warn!("{}", problem);
+                        link_loops += 1;
+                    }
+                    (rec, e.depth())
+                }
+                None => return Err(e.into()),
+            },
+        };
 
-        let metainfo = rec.metadata()?;
+        let metainfo = entry_metadata(&rec, dereference)?;
         let mut buffer: Vec<&[u8]> = vec![];
         let mut buffer_ansi: Vec<String> = vec![];
 
+        let kind = FileKind::of(&rec, &metainfo);
         if show_type {
-            let file_type = if rec.file_type().is_dir() {
-                "dir"
-            } else if rec.file_type().is_file() {
-                "file"
-            } else if rec.file_type().is_symlink() {
-                "symlink"
-            } else {
-                "other"
-            };
+            let file_type = kind.label();
             if is_tty {
-                if file_type == "dir" {
-                    buffer_ansi.push(format!("{COLOR_BLUE}dir{COLOR_RESET}\t"));
-                } else if file_type == "symlink" {
-                    buffer_ansi.push(format!("{COLOR_CYAN}symlink{COLOR_RESET}\t"));
-                } else if file_type == "file" {
-                    buffer_ansi.push(format!("{COLOR_WHITE}file{COLOR_RESET}\t"));
-                } else {
-                    buffer_ansi.push(format!("{COLOR_RED}other{COLOR_RESET}\t"));
-                }
+                let color = match kind {
+                    FileKind::Dir => COLOR_BLUE,
+                    FileKind::Symlink => COLOR_CYAN,
+                    FileKind::File => COLOR_WHITE,
+                    FileKind::Executable => COLOR_GREEN,
+                    FileKind::Broken => COLOR_RED,
+                    _ => COLOR_YELLOW,
+                };
+                buffer_ansi.push(format!("{color}{file_type}{COLOR_RESET}\t"));
             } else {
                 buffer.push(file_type.as_bytes());
                 buffer.push(b"\t");
@@ -161,6 +247,14 @@ where
                 continue;
             }
         }
+        if !owner_filter.is_match(&metainfo) {
+            continue;
+        }
+        // the search root itself is never reported empty, so a cleanup can not remove it
+        if empty_filter.is_active() && (depth == 0 || !empty_filter.is_match(rec.path(), &metainfo))
+        {
+            continue;
+        }
 
         // show file size in output or not
         let mut file_size_tmp = String::new();
@@ -197,27 +291,15 @@ where
         if show_file_name {
             let file_name = rec.file_name().to_str().unwrap();
             if is_tty {
-                let file_extension = rec.path().extension().and_then(|ext| ext.to_str());
-                let colorized_name = match file_extension {
-                    Some("gz") | Some("bz2") | Some("zip") | Some("tar") | Some("xz")
-                    | Some("lz4") | Some("zst") => format!("{COLOR_RED}{}{COLOR_RESET}", file_name),
-                    Some("png") | Some("jpeg") | Some("jpg") | Some("svg") | Some("tiff")
-                    | Some("bmp") => format!("{COLOR_PINK}{}{COLOR_RESET}", file_name),
-                    Some("pdf") | Some("html") | Some("xml") | Some("json") | Some("tsv")
-                    | Some("csv") | Some("xlsx") => {
-                        format!("{COLOR_YELLOW}{}{COLOR_RESET}", file_name)
-                    }
-                    Some("log") | Some("txt") | Some("md") | Some("Md") | Some("MD")
-                    | Some("yaml") | Some("yml") | Some("toml") | Some("ini") => {
-                        format!("{COLOR_CYAN}{}{COLOR_RESET}", file_name)
-                    }
-                    Some("rs") | Some("go") | Some("py") | Some("pl") | Some("java")
-                    | Some("js") | Some("ts") | Some("c") | Some("cpp") | Some("sh")
-                    | Some("bash") | Some("zsh") | Some("fish") | Some("r") | Some("R") => {
-                        format!("{COLOR_GREEN}{}{COLOR_RESET}", file_name)
-                    }
-                    _ => format!("{COLOR_WHITE}{}{COLOR_RESET}", file_name),
+                let color = match group_of(file_name) {
+                    Some("archive") => COLOR_RED,
+                    Some("image") => COLOR_PINK,
+                    Some("document") => COLOR_YELLOW,
+                    Some("text") => COLOR_CYAN,
+                    Some("source") => COLOR_GREEN,
+                    _ => COLOR_WHITE,
                 };
+                let colorized_name = format!("{color}{}{COLOR_RESET}", file_name);
                 buffer_ansi.push(format!("{}\t", colorized_name));
             } else {
                 buffer.push(file_name.as_bytes());
@@ -303,57 +385,147 @@ where
         }
 
         // skip mismatch file type
-        if let Some(typ) = filter_type {
-            if typ == "d" && !rec.file_type().is_dir() {
-                continue;
-            }
-            if typ == "f" && !rec.file_type().is_file() {
-                continue;
-            }
-            if typ == "l" && !rec.file_type().is_symlink() {
-                continue;
-            }
+        if !kind.is_any_of(filter_type) {
+            continue;
         }
 
         // skip mismatch regex
-        if regex_pattern.is_some()
-            && !regex
-                .as_ref()
-                .unwrap()
-                .is_match(file_path.to_str().unwrap())
+        if !regex.is_match(&rec, &root) {
+            continue;
+        }
+
+        // skip entries matching none of the globs
+        if !globs.is_empty() && !globs.is_match(&relative(&rec), rec.file_name()) {
+            continue;
+        }
+
+        // output file type by file extension
+        if !ext_filter.is_match(&rec.file_name().to_string_lossy()) {
+            continue;
+        }
+
+        // skip entries not matching the `--where` expression
+        if where_expr
+            .as_ref()
+            .is_some_and(|expr| !expr.eval(&FileRecord::new(&rec, depth, &root, &metainfo)))
         {
             continue;
         }
 
+        // named capture groups of `-r` as extra columns
+        let mut captures_tmp = String::new();
+        if !regex.capture_names().is_empty() {
+            for value in regex.captures(rec.path(), &root) {
+                captures_tmp.push('\t');
+                captures_tmp.push_str(&value);
+            }
+            if is_tty {
+                buffer_ansi.push(captures_tmp.clone());
+            } else {
+                buffer.push(captures_tmp.as_bytes());
+            }
+        }
+
+        // raw and fully resolved target of symlinks
+        let target_tmp;
+        if link_target {
+            let (raw, resolved) = link_targets(&rec);
+            target_tmp = format!("\t{}\t{}", raw, resolved);
+            if is_tty {
+                buffer_ansi.push(target_tmp.clone());
+            } else {
+                buffer.push(target_tmp.as_bytes());
+            }
+        }
+
+        item_count += 1;
+        matched.push(rec.path().to_path_buf());
+
+        // content matches, kinds, line counts and hash columns are filled in after the scan, so
+        // that files can be read in parallel
+        if content.is_some() || sniffer.is_some() || line_counter.is_some() || hasher.is_some() {
+            if is_tty {
+                pending.push(buffer_ansi.concat());
+            } else {
+                pending.push(String::from_utf8_lossy(&buffer.concat()).into_owned());
+            }
+            continue;
+        }
         if is_tty {
             buffer_ansi.push("\n".to_string());
+            vec_all.push(buffer_ansi.concat());
         } else {
             buffer.push(b"\n");
+            fp.write_all(buffer.concat().as_ref())?;
         }
+    }
 
-        // output file type by file extension
-        if let Some(exten) = extension {
-            if rec
-                .file_name()
-                .to_str()
-                .map(|s| PathBuf::from(s).extension().is_some_and(|ext| ext == exten))
-                .unwrap_or(false)
-            {
-                item_count += 1;
-                if is_tty {
-                    vec_all.push(buffer_ansi.concat());
-                } else {
-                    fp.write_all(buffer.concat().as_ref())?;
-                }
+    // drop the rows of files without a match
+    let mut found: Vec<Vec<Line>> = vec![];
+    if let Some(content) = content {
+        let results = content.search_files(&matched);
+        let rows = std::mem::take(&mut pending)
+            .into_iter()
+            .zip(std::mem::take(&mut matched))
+            .zip(results);
+        for ((row, path), lines) in rows {
+            if let Some(lines) = lines {
+                pending.push(row);
+                matched.push(path);
+                found.push(lines);
             }
-        } else {
-            item_count += 1;
-            if is_tty {
-                vec_all.push(buffer_ansi.concat());
-            } else {
-                fp.write_all(buffer.concat().as_ref())?;
+        }
+        item_count = matched.len();
+    }
+
+    // drop the rows of files of other kinds
+    let mut kinds: Vec<String> = vec![];
+    if let Some(sniffer) = sniffer {
+        let results = sniffer.sniff_files(&matched);
+        let mut lines = std::mem::take(&mut found).into_iter();
+        let rows = std::mem::take(&mut pending)
+            .into_iter()
+            .zip(std::mem::take(&mut matched))
+            .zip(results);
+        for ((row, path), sniffed) in rows {
+            let found_lines = lines.next();
+            if sniffer.is_match(&sniffed) {
+                pending.push(row);
+                matched.push(path);
+                found.extend(found_lines);
+                kinds.push(sniffer.columns(&sniffed));
             }
         }
+        item_count = matched.len();
+    }
+
+    let line_counts = line_counter.map(|reader| reader.line_counts(&matched));
+    let digests = hasher.map(|hasher| hasher.hash_files(&matched));
+    for (idx, row) in pending.into_iter().enumerate() {
+        let mut line = row;
+        if let Some(kind) = kinds.get(idx) {
+            line.push_str(kind);
+        }
+        if let Some(line_counts) = line_counts.as_ref() {
+            line.push('\t');
+            line.push_str(&line_counts[idx]);
+        }
+        if let Some(digests) = digests.as_ref() {
+            line.push('\t');
+            line.push_str(&digests[idx].join("\t"));
+        }
+        line.push('\n');
+        if let Some(lines) = found.get(idx) {
+            line.push_str(&format_lines(lines, is_tty));
+        }
+        if is_tty {
+            vec_all.push(line);
+        } else {
+            fp.write_all(line.as_bytes())?;
+        }
+    }
+    if let Some(hasher) = hasher {
+        hasher.save_cache()?;
     }
 
     write!(&mut tw, "{}", vec_all.concat())?;
@@ -367,10 +539,141 @@ where
     fp.flush()?;
 
     info!("total item: {}", item_count);
-    Ok(())
+    if link_loops > 0 {
+        warn!("{} symlink loop(s) were not followed", link_loops);
+    }
+    Ok(matched)
 }
 
-fn size_trans(size: f64, fmt: &str) -> String {
+// with `--dereference` the metadata of the link target, or of the link itself if it is broken
+fn entry_metadata(rec: &DirEntry, dereference: bool) -> Result<Metadata, Error> {
+    if dereference && rec.path_is_symlink() {
+        return fs::metadata(rec.path()).or_else(|_| Ok(rec.metadata()?));
+    }
+    Ok(rec.metadata()?)
+}
+
+// the raw and the fully resolved target of a symlink, `-` if there is none
+fn link_targets(rec: &DirEntry) -> (String, String) {
+    if !rec.path_is_symlink() {
+        return ("-".to_string(), "-".to_string());
+    }
+    let raw = fs::read_link(rec.path())
+        .map(|t| t.display().to_string())
+        .unwrap_or_else(|_| "-".to_string());
+    let resolved = fs::canonicalize(rec.path())
+        .map(|t| t.display().to_string())
+        .unwrap_or_else(|_| "-".to_string());
+    (raw, resolved)
+}
+
+// with `-l`, a link that can not be followed is an error of the walk: it is dangling, part of a
+// cycle of links, or points to one of its own ancestors. The link itself is kept as an entry, so
+// that `-T L` finds dangling links, and loops are described for a warning
+fn unfollowed_link(err: &walkdir::Error) -> Option<(DirEntry, Option<String>)> {
+    let path = err.path().filter(|p| p.is_symlink())?;
+    let problem = if let Some(ancestor) = err.loop_ancestor() {
+        Some(format!(
+            "symlink loop: `{}` points to its ancestor `{}`",
+            path.display(),
+            ancestor.display()
+        ))
+    } else if err.io_error().and_then(|e| e.raw_os_error()) == Some(libc::ELOOP) {
+        Some(format!("symlink loop: `{}`", path.display()))
+    } else {
+        None
+    };
+    let entry = WalkDir::new(path)
+        .follow_root_links(false)
+        .into_iter()
+        .next()?
+        .ok()?;
+    Some((entry, problem))
+}
+
+/// Collect the paths of entries with the given type that pass the filters, without listing them.
+/// Ignore files are only respected with `ignore`
+pub fn collect_entries<P>(
+    src: P,
+    filter: &FilterOpts,
+    ignore: Option<&IgnoreOpts>,
+    filter_type: &[FileKind],
+) -> Result<Vec<PathBuf>, Error>
+where
+    P: AsRef<Path> + Clone,
+{
+    search_dir(
+        src,
+        &filter.regex,
+        &filter.not_regex,
+        filter.all_regex,
+        filter.match_on,
+        filter.ignore_case,
+        false,
+        filter.deepth,
+        false,
+        false,
+        filter.file_size_max,
+        filter.file_size_min,
+        "b",
+        false,
+        filter_type,
+        &filter.ext,
+        &filter.exclude_ext,
+        filter.where_expr.as_deref(),
+        filter.owner.as_deref(),
+        filter.group.as_deref(),
+        filter.nouser,
+        filter.nogroup,
+        filter.perm.as_deref(),
+        filter.empty,
+        filter.empty_dirs_recursive,
+        false,
+        false,
+        false,
+        filter.show_link_dir,
+        filter.show_hiden,
+        false,
+        false,
+        ignore.is_none_or(|i| i.no_ignore),
+        ignore.map_or(&[], |i| &i.ignore_file),
+        &filter.glob,
+        &filter.exclude,
+        &filter.prune,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+        false,
+    )
+}
+
+// matching lines below the row of a file, `number:text` for matches and `number-text` for
+// context lines, with `--` between groups that are not adjacent
+fn format_lines(lines: &[Line], is_tty: bool) -> String {
+    let mut out = String::new();
+    let mut prev: Option<usize> = None;
+    for line in lines {
+        if prev.is_some_and(|p| p + 1 != line.number) {
+            out.push_str("  --\n");
+        }
+        let sep = if line.is_match { ':' } else { '-' };
+        if is_tty {
+            out.push_str(&format!(
+                "  {COLOR_GREEN}{}{COLOR_RESET}{}{}\n",
+                line.number, sep, line.text
+            ));
+        } else {
+            out.push_str(&format!("  {}{}{}\n", line.number, sep, line.text));
+        }
+        prev = Some(line.number);
+    }
+    out
+}
+
+pub fn size_trans(size: f64, fmt: &str) -> String {
     let kb = 1024f64;
     let mb = 1024. * kb;
     let gb = 1024. * mb;
@@ -414,7 +717,7 @@ fn time_trans(seconds: u64) -> String {
     time_string
 }
 
-fn is_hidden(entry: &DirEntry) -> bool {
+pub fn is_hidden(entry: &DirEntry) -> bool {
     entry
         .file_name()
         .to_str()
@@ -425,6 +728,7 @@ fn is_hidden(entry: &DirEntry) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::temp::TempDir;
 
     #[test]
     fn test_size_trans() {
@@ -439,4 +743,57 @@ mod tests {
         assert_eq!(time_trans(86461), "1d1m1s");
         assert_eq!(time_trans(86401), "1d1s");
     }
+
+    #[test]
+    fn test_unfollowed_link() {
+        use std::os::unix::fs::symlink;
+
+        let base = TempDir::new("process");
+        fs::create_dir_all(base.join("sub")).unwrap();
+        symlink("missing", base.join("dang")).unwrap();
+        symlink("cycle-b", base.join("cycle-a")).unwrap();
+        symlink("cycle-a", base.join("cycle-b")).unwrap();
+        symlink("..", base.join("sub").join("up")).unwrap();
+        fs::write(base.join("sub").join("data"), b"12345").unwrap();
+        symlink("sub/data", base.join("data")).unwrap();
+
+        // the link targets and the metadata with and without `--dereference`
+        let entry = |name: &str| WalkDir::new(base.join(name)).follow_root_links(false);
+        let data = entry("data").into_iter().next().unwrap().unwrap();
+        let resolved = fs::canonicalize(base.join("sub").join("data")).unwrap();
+        assert_eq!(
+            link_targets(&data),
+            ("sub/data".to_string(), resolved.display().to_string())
+        );
+        assert!(entry_metadata(&data, false).unwrap().is_symlink());
+        assert_eq!(entry_metadata(&data, true).unwrap().len(), 5);
+        let dang = entry("dang").into_iter().next().unwrap().unwrap();
+        assert_eq!(
+            link_targets(&dang),
+            ("missing".to_string(), "-".to_string())
+        );
+        assert!(entry_metadata(&dang, true).unwrap().is_symlink());
+
+        let mut links: Vec<(String, &str, bool)> = WalkDir::new(&base)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|e| e.err())
+            .map(|e| {
+                let (rec, problem) = unfollowed_link(&e).unwrap();
+                let kind = FileKind::of(&rec, &rec.metadata().unwrap());
+                let name = rec.file_name().to_string_lossy().into_owned();
+                (name, kind.label(), problem.is_some())
+            })
+            .collect();
+        links.sort();
+        assert_eq!(
+            links,
+            vec![
+                ("cycle-a".to_string(), "broken", true),
+                ("cycle-b".to_string(), "broken", true),
+                ("dang".to_string(), "broken", false),
+                ("up".to_string(), "symlink", true),
+            ]
+        );
+    }
 }
@@ -0,0 +1,399 @@
+//! Batch rename by regex.
+//!
+//! All renames are checked before anything is touched: new names must be valid, no two entries
+//! may get the same name and a new name must not replace an entry that is not renamed itself.
+//! Entries in deeper directories are renamed first, so the paths of their parents stay valid.
+//! Within a directory a rename waits until its new name has been freed by another rename, and
+//! cycles like `a -> b, b -> a` are broken by moving one entry to a temporary name first.
+//!
+//! Every rename is recorded in a journal as soon as it is done, so a batch can be reverted
+//! even if it stopped halfway. The journal starts with the line `# fdx rename journal v1`,
+//! followed by one `from  to` line per rename with absolute paths separated by a tab, escaped
+//! like the `fdx` manifest format.
+
+use log::{error, info};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Error, ErrorKind, Write},
+    os::unix::ffi::OsStrExt,
+    path::{self, Path, PathBuf},
+};
+use tabwriter::TabWriter;
+
+use crate::{
+    cli::RenameOpt,
+    delete::confirm,
+    manifest::{escape, unescape},
+    process::collect_entries,
+};
+
+const JOURNAL_HEADER: &str = "# fdx rename journal v1";
+
+// matched entries and their new paths, invalid new names are reported as problems
+fn plan(
+    paths: &[PathBuf],
+    root: &Path,
+    from: &Regex,
+    to: &str,
+    replace_all: bool,
+    problems: &mut Vec<String>,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut renames = vec![];
+    for path in paths.iter().filter(|p| p.as_path() != root) {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let new = if replace_all {
+            from.replace_all(name, to)
+        } else {
+            from.replace(name, to)
+        };
+        if new == name {
+            continue;
+        }
+        if new.is_empty() || new == "." || new == ".." || new.contains('/') || new.contains('\0') {
+            problems.push(format!(
+                "invalid new name `{}` for `{}`",
+                new,
+                path.display()
+            ));
+            continue;
+        }
+        renames.push((path.clone(), path.with_file_name(new.as_ref())));
+    }
+    renames
+}
+
+// collisions between new names, and new names that replace entries which are not renamed
+fn check(renames: &[(PathBuf, PathBuf)], problems: &mut Vec<String>) {
+    let sources: HashSet<&Path> = renames.iter().map(|(from, _)| from.as_path()).collect();
+    let mut targets: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for (from, to) in renames {
+        targets.entry(to).or_default().push(from);
+    }
+    for (from, to) in renames {
+        let others = &targets[to.as_path()];
+        if others.len() > 1 {
+            if others[0] == from {
+                let names: Vec<String> = others
+                    .iter()
+                    .map(|p| format!("`{}`", p.display()))
+                    .collect();
+                problems.push(format!(
+                    "{} would all be renamed to `{}`",
+                    names.join(", "),
+                    to.display()
+                ));
+            }
+        } else if to.symlink_metadata().is_ok() && !sources.contains(to.as_path()) {
+            problems.push(format!(
+                "`{}` can not be renamed to `{}`, which already exists",
+                from.display(),
+                to.display()
+            ));
+        }
+    }
+}
+
+fn temp_name(path: &Path, n: usize) -> PathBuf {
+    let mut n = n;
+    loop {
+        let tmp = path.with_file_name(format!(".fdx-rename-{}-{}.tmp", std::process::id(), n));
+        if tmp.symlink_metadata().is_err() {
+            return tmp;
+        }
+        n += 1;
+    }
+}
+
+// the actual renames in a safe order, and the number of cycles that needed a temporary name
+fn order(renames: &[(PathBuf, PathBuf)]) -> (Vec<(PathBuf, PathBuf)>, usize) {
+    let mut dirs: HashMap<&Path, Vec<(PathBuf, PathBuf)>> = HashMap::new();
+    for (from, to) in renames {
+        dirs.entry(from.parent().unwrap_or(Path::new("")))
+            .or_default()
+            .push((from.clone(), to.clone()));
+    }
+    let mut dirs: Vec<_> = dirs.into_iter().collect();
+    dirs.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+
+    let (mut steps, mut cycles) = (vec![], 0usize);
+    for (_, mut pending) in dirs {
+        let mut busy: HashSet<PathBuf> = pending.iter().map(|(from, _)| from.clone()).collect();
+        while !pending.is_empty() {
+            if let Some(idx) = pending.iter().position(|(_, to)| !busy.contains(to)) {
+                let (from, to) = pending.remove(idx);
+                busy.remove(&from);
+                steps.push((from, to));
+            } else {
+                // every new name is still taken by another entry of the batch: a cycle
+                let tmp = temp_name(&pending[0].0, cycles);
+                busy.remove(&pending[0].0);
+                steps.push((pending[0].0.clone(), tmp.clone()));
+                pending[0].0 = tmp;
+                cycles += 1;
+            }
+        }
+    }
+    (steps, cycles)
+}
+
+// rename without replacing an existing entry
+fn rename_noreplace(from: &Path, to: &Path) -> Result<(), Error> {
+    let (src, dst) = (
+        CString::new(from.as_os_str().as_bytes())?,
+        CString::new(to.as_os_str().as_bytes())?,
+    );
+    let ret = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            src.as_ptr(),
+            libc::AT_FDCWD,
+            dst.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+    let e = Error::last_os_error();
+    match e.raw_os_error() {
+        // the filesystem or kernel does not support `RENAME_NOREPLACE`
+        Some(libc::EINVAL) | Some(libc::ENOSYS) => {
+            if to.symlink_metadata().is_ok() {
+                return Err(Error::from(ErrorKind::AlreadyExists));
+            }
+            fs::rename(from, to)
+        }
+        _ => Err(e),
+    }
+}
+
+// rename in order, recording each finished rename in the journal
+fn apply(steps: &[(PathBuf, PathBuf)], journal: &Path) -> Result<(), Error> {
+    let mut fp = BufWriter::new(File::create_new(journal)?);
+    writeln!(fp, "{}", JOURNAL_HEADER)?;
+    fp.flush()?;
+    for (from, to) in steps {
+        rename_noreplace(from, to).map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!(
+                    "failed to rename `{}` to `{}`: {}",
+                    from.display(),
+                    to.display(),
+                    e
+                ),
+            )
+        })?;
+        let (from, to) = (path::absolute(from)?, path::absolute(to)?);
+        writeln!(
+            fp,
+            "{}\t{}",
+            escape(&from.to_string_lossy()),
+            escape(&to.to_string_lossy())
+        )?;
+        fp.flush()?;
+    }
+    Ok(())
+}
+
+fn read_journal(journal: &Path) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+    let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+    let mut lines = BufReader::new(File::open(journal)?).lines();
+    if lines.next().transpose()?.as_deref() != Some(JOURNAL_HEADER) {
+        return Err(invalid(format!(
+            "`{}` is not a rename journal",
+            journal.display()
+        )));
+    }
+    let mut steps = vec![];
+    for (idx, line) in lines.enumerate() {
+        let line = line?;
+        let Some((from, to)) = line.split_once('\t') else {
+            return Err(invalid(format!(
+                "invalid journal line {} in `{}`",
+                idx + 2,
+                journal.display()
+            )));
+        };
+        steps.push((PathBuf::from(unescape(from)), PathBuf::from(unescape(to))));
+    }
+    Ok(steps)
+}
+
+fn preview(renames: &[(PathBuf, PathBuf)], root: &Path, header: bool) -> Result<(), Error> {
+    let mut tw = TabWriter::new(BufWriter::new(io::stdout()));
+    if !header {
+        writeln!(tw, "From\tTo")?;
+    }
+    for (from, to) in renames {
+        let rel = |p: &Path| p.strip_prefix(root).unwrap_or(p).display().to_string();
+        writeln!(tw, "{}\t{}", rel(from), rel(to))?;
+    }
+    tw.flush()
+}
+
+// Some(true) to go on, Some(false) if the user declined, None if there is nobody to ask
+fn confirmed(yes: bool) -> Result<Option<bool>, Error> {
+    if yes {
+        return Ok(Some(true));
+    }
+    let answer = confirm()?;
+    match answer {
+        Some(false) => eprintln!("aborted, nothing was renamed"),
+        None => eprintln!(
+            "error: refuse to rename without confirmation, use `--yes` in non-interactive mode"
+        ),
+        _ => {}
+    }
+    Ok(answer)
+}
+
+fn undo(opt: &RenameOpt, journal: &Path) -> Result<bool, Error> {
+    let steps: Vec<(PathBuf, PathBuf)> = read_journal(journal)?
+        .into_iter()
+        .rev()
+        .map(|(from, to)| (to, from))
+        .collect();
+    preview(&steps, Path::new(""), opt.header)?;
+    eprintln!("revert {} rename(s)", steps.len());
+    if opt.dry_run || steps.is_empty() {
+        return Ok(true);
+    }
+    match confirmed(opt.yes)? {
+        Some(true) => {}
+        Some(false) => return Ok(true),
+        None => return Ok(false),
+    }
+
+    for (idx, (from, to)) in steps.iter().enumerate() {
+        if let Err(e) = rename_noreplace(from, to) {
+            error!(
+                "failed to rename `{}` back to `{}`: {}",
+                from.display(),
+                to.display(),
+                e
+            );
+            error!("{} of {} rename(s) reverted", idx, steps.len());
+            return Ok(false);
+        }
+    }
+    info!("{} rename(s) reverted", steps.len());
+    Ok(true)
+}
+
+pub fn rename(opt: &RenameOpt) -> Result<bool, Error> {
+    if let Some(journal) = opt.undo.as_ref() {
+        return undo(opt, Path::new(journal));
+    }
+    let root = Path::new(opt.rootdir.as_deref().unwrap_or("."));
+    if !root.is_dir() {
+        eprintln!("error: dir `{}` not exists", root.display());
+        return Ok(false);
+    }
+    let (Some(from), Some(to)) = (opt.from.as_ref(), opt.to.as_ref()) else {
+        unreachable!("`--from` and `--to` are required by clap");
+    };
+    let from = Regex::new(from).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid regex `{}`: {}", from, e),
+        )
+    })?;
+
+    let paths = collect_entries(root, &opt.filter, Some(&opt.ignore), &opt.filetype)?;
+    let mut problems = vec![];
+    let renames = plan(&paths, root, &from, to, opt.replace_all, &mut problems);
+    check(&renames, &mut problems);
+    preview(&renames, root, opt.header)?;
+    if !problems.is_empty() {
+        for problem in problems.iter() {
+            eprintln!("error: {}", problem);
+        }
+        eprintln!("nothing was renamed");
+        return Ok(false);
+    }
+
+    let (steps, cycles) = order(&renames);
+    eprintln!("rename {} entries", renames.len());
+    if cycles > 0 {
+        info!("{} cycle(s) are resolved through temporary names", cycles);
+    }
+    if opt.dry_run || renames.is_empty() {
+        return Ok(true);
+    }
+    match confirmed(opt.yes)? {
+        Some(true) => {}
+        Some(false) => return Ok(true),
+        None => return Ok(false),
+    }
+
+    let journal = match opt.journal.as_ref() {
+        Some(journal) => PathBuf::from(journal),
+        None => root.join(format!(
+            ".fdx-rename-{}.journal",
+            jiff::Zoned::now().strftime("%Y%m%dT%H%M%S")
+        )),
+    };
+    if let Err(e) = apply(&steps, &journal) {
+        error!("{}", e);
+        error!(
+            "the batch stopped halfway, revert it with `fdx rename --undo {}`",
+            journal.display()
+        );
+        return Ok(false);
+    }
+    info!(
+        "{} entries renamed, undo with `fdx rename --undo {}`",
+        renames.len(),
+        journal.display()
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_rename_cycle_and_undo() {
+        let base = TempDir::new("rename");
+        fs::create_dir_all(base.join("d")).unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(base.join("d").join(name), name).unwrap();
+        }
+        let p = |rel: &str| base.join(rel);
+        // a -> b -> c -> a, and the dir itself after its contents
+        let renames = vec![
+            (p("d"), p("e")),
+            (p("d/a"), p("d/b")),
+            (p("d/b"), p("d/c")),
+            (p("d/c"), p("d/a")),
+        ];
+        let mut problems = vec![];
+        check(&renames, &mut problems);
+        assert!(problems.is_empty(), "{:?}", problems);
+        let (steps, cycles) = order(&renames);
+        assert_eq!(cycles, 1);
+
+        let journal = base.join("journal");
+        apply(&steps, &journal).unwrap();
+        let read = |rel: &str| fs::read_to_string(base.join(rel)).unwrap();
+        assert_eq!([read("e/a"), read("e/b"), read("e/c")], ["c", "a", "b"]);
+
+        for (from, to) in read_journal(&journal).unwrap().into_iter().rev() {
+            rename_noreplace(&to, &from).unwrap();
+        }
+        let restored = [read("d/a"), read("d/b"), read("d/c")];
+        assert_eq!(restored, ["a", "b", "c"]);
+
+        let mut problems = vec![];
+        check(&[(p("x"), p("z")), (p("y"), p("z"))], &mut problems);
+        assert_eq!(problems.len(), 1);
+    }
+}
@@ -0,0 +1,261 @@
+use log::{info, warn};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+use tabwriter::TabWriter;
+
+use crate::{cli::OutFormat, pattern::RegexFilter, process::size_trans};
+
+pub struct ReportOptions<'a> {
+    pub format: OutFormat,
+    pub group_by: Option<&'a str>,
+    pub no_header: bool,
+    pub out: Option<&'a String>,
+    pub size_fmt: &'a str,
+}
+
+/// Write matched entries with the named capture groups of `-r` as fields, one record per entry,
+/// or one per value of the `--group-by` group. Returns false if an entry could not be read
+pub fn report_entries(
+    root: &Path,
+    paths: &[PathBuf],
+    regex: &RegexFilter,
+    opts: &ReportOptions,
+) -> Result<bool, Error> {
+    if let Some(name) = opts.group_by {
+        if !regex.capture_names().iter().any(|n| n == name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("`--group-by {}` is not a named capture group of -r", name),
+            ));
+        }
+    }
+
+    let mut fp: Box<dyn Write> = match (opts.out, opts.format) {
+        (Some(out), _) => Box::new(BufWriter::new(File::create(out)?)),
+        (None, OutFormat::Table) => Box::new(TabWriter::new(BufWriter::new(io::stdout()))),
+        (None, _) => Box::new(BufWriter::new(io::stdout())),
+    };
+    let ok = match opts.group_by {
+        Some(name) => write_groups(&mut fp, root, paths, regex, name, opts)?,
+        None => {
+            write_records(&mut fp, root, paths, regex, opts)?;
+            true
+        }
+    };
+    fp.flush()?;
+    Ok(ok)
+}
+
+fn write_records(
+    fp: &mut dyn Write,
+    root: &Path,
+    paths: &[PathBuf],
+    regex: &RegexFilter,
+    opts: &ReportOptions,
+) -> Result<(), Error> {
+    let mut header = vec!["path".to_string()];
+    header.extend(regex.capture_names().iter().cloned());
+    let rows: Vec<Vec<Value>> = paths
+        .iter()
+        .map(|path| {
+            let mut row = vec![Value::from(path.to_string_lossy())];
+            row.extend(regex.captures(path, root).into_iter().map(Value::from));
+            row
+        })
+        .collect();
+    write_rows(fp, &header, &rows, opts)
+}
+
+// entries and total size per captured value, entries the group did not match are counted under
+// an empty value
+fn write_groups(
+    fp: &mut dyn Write,
+    root: &Path,
+    paths: &[PathBuf],
+    regex: &RegexFilter,
+    name: &str,
+    opts: &ReportOptions,
+) -> Result<bool, Error> {
+    let idx = regex
+        .capture_names()
+        .iter()
+        .position(|n| n == name)
+        .unwrap_or_default();
+    let mut ok = true;
+    let mut groups: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    for path in paths {
+        let meta = match fs::symlink_metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                warn!("failed to read `{}`: {}", path.display(), e);
+                ok = false;
+                continue;
+            }
+        };
+        let group = groups
+            .entry(regex.captures(path, root).swap_remove(idx))
+            .or_default();
+        group.0 += 1;
+        if !meta.is_dir() {
+            group.1 += meta.len();
+        }
+    }
+    info!("{} group(s) of `{}`", groups.len(), name);
+
+    let header = [name.to_string(), "entries".to_string(), "size".to_string()];
+    let rows: Vec<Vec<Value>> = groups
+        .into_iter()
+        .map(|(value, (entries, bytes))| {
+            let size = match opts.format {
+                OutFormat::Table => Value::from(size_trans(bytes as f64, opts.size_fmt)),
+                _ => Value::from(bytes),
+            };
+            vec![Value::from(value), Value::from(entries), size]
+        })
+        .collect();
+    write_rows(fp, &header, &rows, opts)?;
+    Ok(ok)
+}
+
+fn write_rows(
+    fp: &mut dyn Write,
+    header: &[String],
+    rows: &[Vec<Value>],
+    opts: &ReportOptions,
+) -> Result<(), Error> {
+    // strings without quotes, numbers as they are
+    let cells = |row: &[Value]| -> Vec<String> {
+        row.iter()
+            .map(|v| match v {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            })
+            .collect()
+    };
+    match opts.format {
+        OutFormat::Table => {
+            if !opts.no_header {
+                writeln!(fp, "{}", header.join("\t"))?;
+            }
+            for row in rows {
+                let row: Vec<String> = cells(row)
+                    .into_iter()
+                    .map(|v| if v.is_empty() { "-".to_string() } else { v })
+                    .collect();
+                writeln!(fp, "{}", row.join("\t"))?;
+            }
+        }
+        OutFormat::Csv => {
+            if !opts.no_header {
+                writeln!(fp, "{}", csv_row(header))?;
+            }
+            for row in rows {
+                writeln!(fp, "{}", csv_row(&cells(row)))?;
+            }
+        }
+        OutFormat::Json => {
+            let json: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    let fields = header.iter().zip(row).map(|(k, v)| (k.clone(), v.clone()));
+                    Value::Object(Map::from_iter(fields))
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut *fp, &json)?;
+            writeln!(fp)?;
+        }
+    }
+    Ok(())
+}
+
+/// Join fields into a CSV line, quoting fields with commas, quotes or line breaks
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            let f = f.as_ref();
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::MatchOn;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_report() {
+        let regex = RegexFilter::new(
+            &[r"(?P<sample>[^_]+)_S\d+_(?P<lane>L\d+)".to_string()],
+            &[],
+            false,
+            MatchOn::Name,
+            false,
+        )
+        .unwrap();
+        let root = Path::new("fq");
+        let paths = vec![
+            PathBuf::from("fq/NA1_S1_L001_R1.fq"),
+            PathBuf::from("fq/NA1_S1_L002_R1.fq"),
+            PathBuf::from("fq/a,b_S2_L001_R1.fq"),
+        ];
+        let opts = ReportOptions {
+            format: OutFormat::Csv,
+            group_by: None,
+            no_header: false,
+            out: None,
+            size_fmt: "b",
+        };
+        let mut out = vec![];
+        write_records(&mut out, root, &paths, &regex, &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "path,sample,lane\n\
+             fq/NA1_S1_L001_R1.fq,NA1,L001\n\
+             fq/NA1_S1_L002_R1.fq,NA1,L002\n\
+             \"fq/a,b_S2_L001_R1.fq\",\"a,b\",L001\n"
+        );
+
+        let opts = ReportOptions {
+            format: OutFormat::Json,
+            ..opts
+        };
+        let mut out = vec![];
+        write_records(&mut out, root, &paths[..1], &regex, &opts).unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["sample"], "NA1");
+        assert_eq!(json[0]["lane"], "L001");
+
+        // missing files are skipped and reported, the other entries are grouped
+        let base = TempDir::new("report");
+        let paths: Vec<PathBuf> = ["NA1_S1_L001.fq", "NA1_S1_L002.fq", "NA2_S2_L001.fq"]
+            .iter()
+            .map(|name| base.join(name))
+            .collect();
+        fs::write(&paths[0], "abc").unwrap();
+        fs::write(&paths[1], "de").unwrap();
+        let opts = ReportOptions {
+            format: OutFormat::Csv,
+            ..opts
+        };
+        let mut out = vec![];
+        let ok = write_groups(&mut out, &base, &paths, &regex, "sample", &opts).unwrap();
+        assert!(!ok);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "sample,entries,size\nNA1,2,5\n"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+/// Create something at a new temporary name next to `dest`, like `.name.fdx-tmp-<pid>-<n>`, to be
+/// renamed over `dest` once complete. `create` must fail with `AlreadyExists` if the name is
+/// taken, then the next name is tried, so a file that is already there is never touched
+pub fn create_temp_with<T, F>(dest: &Path, mut create: F) -> Result<(PathBuf, T), Error>
+where
+    F: FnMut(&Path) -> Result<T, Error>,
+{
+    let mut n = 0usize;
+    loop {
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(dest.file_name().unwrap_or_default());
+        tmp_name.push(format!(".fdx-tmp-{}-{}", std::process::id(), n));
+        let tmp = dest.with_file_name(tmp_name);
+        match create(&tmp) {
+            Ok(created) => return Ok((tmp, created)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A new empty file at a temporary name next to `dest`
+pub fn create_temp(dest: &Path) -> Result<(PathBuf, File), Error> {
+    create_temp_with(dest, |tmp| File::create_new(tmp))
+}
+
+/// A directory under the system temp dir with a unique name, removed with its content on drop
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let dir =
+            std::env::temp_dir().join(format!("fdx-{}-test-{}-{}", name, std::process::id(), n));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
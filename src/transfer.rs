@@ -0,0 +1,302 @@
+use clap::ValueEnum;
+use log::{error, info, warn};
+use std::{
+    ffi::OsString,
+    fs::{self, File, FileTimes},
+    io::{self, Error, ErrorKind},
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+use crate::temp::{create_temp, create_temp_with};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Conflict {
+    /// Keep the existing destination file
+    Skip,
+    /// Replace the existing destination file
+    Overwrite,
+    /// Write to a new name like `file.1.txt`
+    Rename,
+    /// Replace the destination only if the source is newer
+    Newer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Copy,
+    Move,
+}
+
+/// Copy or move matched entries into `dest`, keeping their path relative to `root`.
+/// Returns true if every entry was handled
+pub fn transfer_entries(
+    root: &Path,
+    paths: &[PathBuf],
+    dest: &Path,
+    mode: Mode,
+    conflict: Conflict,
+) -> Result<bool, Error> {
+    let (mut done, mut skipped, mut success) = (0usize, 0usize, true);
+    // mode and times of directories are restored once their contents are in place, moved source
+    // directories are removed then
+    let mut dirs: Vec<(&Path, PathBuf, fs::Metadata)> = vec![];
+
+    for path in paths {
+        let rel = match path.strip_prefix(root) {
+            Ok(rel) if !rel.as_os_str().is_empty() => rel,
+            // the scan root itself
+            _ => continue,
+        };
+        let meta = fs::symlink_metadata(path)?;
+        let mut target = dest.join(rel);
+
+        // only the directory itself is recreated, its contents are handled as separate entries
+        if meta.is_dir() {
+            match fs::create_dir_all(&target) {
+                Ok(()) => dirs.push((path, target, meta)),
+                Err(e) => {
+                    error!("failed to create dir `{}`: {}", target.display(), e);
+                    success = false;
+                }
+            }
+            continue;
+        }
+
+        if fs::symlink_metadata(&target).is_ok() {
+            match conflict {
+                Conflict::Skip => {
+                    skipped += 1;
+                    continue;
+                }
+                Conflict::Newer => {
+                    let dst_mtime = fs::symlink_metadata(&target)?.modified()?;
+                    if meta.modified()? <= dst_mtime {
+                        skipped += 1;
+                        continue;
+                    }
+                }
+                Conflict::Overwrite => {}
+                Conflict::Rename => target = free_name(&target),
+            }
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let res = match mode {
+            Mode::Copy => copy_entry(path, &target, &meta),
+            Mode::Move => move_entry(path, &target, &meta),
+        };
+        match res {
+            Ok(()) => done += 1,
+            Err(e) => {
+                error!(
+                    "failed to {} `{}` to `{}`: {}",
+                    if mode == Mode::Copy { "copy" } else { "move" },
+                    path.display(),
+                    target.display(),
+                    e
+                );
+                success = false;
+            }
+        }
+    }
+
+    for (_, target, meta) in dirs.iter().rev() {
+        if let Err(e) =
+            set_times(target, meta).and_then(|_| fs::set_permissions(target, meta.permissions()))
+        {
+            warn!(
+                "failed to keep mode and times of `{}`: {}",
+                target.display(),
+                e
+            );
+        }
+    }
+    if mode == Mode::Move {
+        success &= remove_source_dirs(dirs.iter().map(|(src, _, _)| *src).collect());
+    }
+
+    info!(
+        "{} {} item(s), skip {} existing item(s)",
+        if mode == Mode::Copy {
+            "copied"
+        } else {
+            "moved"
+        },
+        done,
+        skipped
+    );
+    if skipped > 0 && conflict == Conflict::Skip {
+        warn!("use `--conflict` to overwrite or rename existing items");
+    }
+    Ok(success)
+}
+
+// `dir/name.ext` -> `dir/name.1.ext`, `dir/name.2.ext`, ...
+fn free_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default();
+    let ext = path.extension();
+    (1..)
+        .map(|idx| {
+            let mut name: OsString = stem.to_os_string();
+            name.push(format!(".{}", idx));
+            if let Some(ext) = ext {
+                name.push(".");
+                name.push(ext);
+            }
+            path.with_file_name(name)
+        })
+        .find(|p| fs::symlink_metadata(p).is_err())
+        .unwrap()
+}
+
+fn set_times(path: &Path, meta: &fs::Metadata) -> Result<(), Error> {
+    let times = FileTimes::new()
+        .set_accessed(meta.accessed()?)
+        .set_modified(meta.modified()?);
+    File::open(path)?.set_times(times)
+}
+
+// the copy is written to a new temporary name next to the destination and renamed over it, so
+// an existing destination symlink is replaced instead of written through
+fn copy_entry(src: &Path, dst: &Path, meta: &fs::Metadata) -> Result<(), Error> {
+    let link = if meta.is_symlink() {
+        Some(fs::read_link(src)?)
+    } else if meta.is_file() {
+        None
+    } else {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "not a regular file or symlink",
+        ));
+    };
+
+    let (tmp, res) = match link {
+        Some(link) => (create_temp_with(dst, |tmp| symlink(&link, tmp))?.0, Ok(())),
+        None => {
+            let (tmp, fp) = create_temp(dst)?;
+            let res = copy_file(src, fp, meta);
+            (tmp, res)
+        }
+    };
+    if let Err(e) = res.and_then(|_| fs::rename(&tmp, dst)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+// data, permission bits and times of `src` into the empty temporary file
+fn copy_file(src: &Path, mut tmp_fp: File, meta: &fs::Metadata) -> Result<(), Error> {
+    let mut src_fp = File::open(src)?;
+    io::copy(&mut src_fp, &mut tmp_fp)?;
+    tmp_fp.set_permissions(meta.permissions())?;
+    tmp_fp.set_times(
+        FileTimes::new()
+            .set_accessed(meta.accessed()?)
+            .set_modified(meta.modified()?),
+    )
+}
+
+// deepest first, so every directory is empty once its contents are moved. A directory that still
+// holds skipped or unmatched entries is kept
+fn remove_source_dirs(mut dirs: Vec<&Path>) -> bool {
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    let mut success = true;
+    for dir in dirs {
+        match fs::remove_dir(dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::DirectoryNotEmpty => {
+                warn!("keep source dir `{}`, it is not empty", dir.display());
+            }
+            Err(e) => {
+                error!("failed to remove source dir `{}`: {}", dir.display(), e);
+                success = false;
+            }
+        }
+    }
+    success
+}
+
+fn move_entry(src: &Path, dst: &Path, meta: &fs::Metadata) -> Result<(), Error> {
+    match fs::rename(src, dst) {
+        Ok(()) => Ok(()),
+        // rename does not work across filesystems, copy the data and remove the source instead
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_entry(src, dst, meta)?;
+            fs::remove_file(src)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_free_name() {
+        let path = Path::new("/nonexistent/dir/report.html");
+        assert_eq!(free_name(path), Path::new("/nonexistent/dir/report.1.html"));
+        let path = Path::new("/nonexistent/dir/README");
+        assert_eq!(free_name(path), Path::new("/nonexistent/dir/README.1"));
+    }
+
+    #[test]
+    fn test_copy_keeps_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, SystemTime};
+
+        let root = TempDir::new("transfer");
+        let (src, dest) = (root.join("src"), root.join("dest"));
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(src.join("sub/r.html"), "new").unwrap();
+        fs::write(root.join("victim"), "keep").unwrap();
+        fs::create_dir_all(dest.join("sub")).unwrap();
+        symlink(root.join("victim"), dest.join("sub/r.html")).unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o750)).unwrap();
+        File::open(src.join("sub"))
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(old))
+            .unwrap();
+
+        let paths = vec![src.join("sub"), src.join("sub/r.html")];
+        assert!(transfer_entries(&src, &paths, &dest, Mode::Copy, Conflict::Overwrite).unwrap());
+        // the destination link is replaced, not written through
+        assert_eq!(fs::read_to_string(root.join("victim")).unwrap(), "keep");
+        let copied = fs::symlink_metadata(dest.join("sub/r.html")).unwrap();
+        assert!(copied.is_file());
+        assert_eq!(fs::read_to_string(dest.join("sub/r.html")).unwrap(), "new");
+        let dir = fs::metadata(dest.join("sub")).unwrap();
+        assert_eq!(dir.permissions().mode() & 0o777, 0o750);
+        assert_eq!(dir.modified().unwrap(), old);
+    }
+
+    #[test]
+    fn test_move_removes_source_dirs() {
+        let root = TempDir::new("transfer-move");
+        let (src, dest) = (root.join("src"), root.join("dest"));
+        fs::create_dir_all(src.join("a/b")).unwrap();
+        fs::create_dir_all(src.join("keep")).unwrap();
+        fs::write(src.join("a/b/r.html"), "r").unwrap();
+        fs::write(src.join("a/s.html"), "s").unwrap();
+        fs::write(src.join("keep/x.txt"), "x").unwrap();
+
+        // `keep/x.txt` is not matched, so `keep` stays
+        let paths: Vec<PathBuf> = ["a", "a/b", "a/b/r.html", "a/s.html", "keep"]
+            .iter()
+            .map(|p| src.join(p))
+            .collect();
+        assert!(transfer_entries(&src, &paths, &dest, Mode::Move, Conflict::Skip).unwrap());
+        assert!(!src.join("a").exists());
+        assert!(src.join("keep/x.txt").exists());
+        assert_eq!(fs::read_to_string(dest.join("a/b/r.html")).unwrap(), "r");
+        assert_eq!(fs::read_to_string(dest.join("a/s.html")).unwrap(), "s");
+        assert!(dest.join("keep").is_dir());
+    }
+}